@@ -0,0 +1,206 @@
+use layout_corrector::dictionary::{build_fst, front_code};
+use layout_corrector::files::write_atomically;
+use layout_corrector::ngram::{corpus_words, NgramModel};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+fn usage(exec_name: &str) {
+    println!(
+        "Usage: {} --language code --output-dir dir [--min-count n] [--top n] corpus_file...\n\
+         \n\
+         Writes code.ngram, the trigram model of the corpora, code.words, their words\n\
+         ranked by frequency, and code.dict and code.fst, the front-coded and the FST\n\
+         dictionary of the words that pass --min-count and --top.",
+        exec_name
+    );
+}
+
+struct Args {
+    language: String,
+    output_dir: String,
+    min_count: u64,
+    top: Option<usize>,
+    corpora: Vec<String>,
+}
+
+fn parse_args(argv: &[String]) -> Option<Args> {
+    let mut language = None;
+    let mut output_dir = None;
+    let mut min_count = 1;
+    let mut top = None;
+    let mut corpora = Vec::new();
+    let mut iter = argv.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--language" => language = Some(iter.next()?.clone()),
+            "--output-dir" => output_dir = Some(iter.next()?.clone()),
+            "--min-count" => min_count = iter.next()?.parse().ok()?,
+            "--top" => top = Some(iter.next()?.parse().ok()?),
+            _ if arg.starts_with("--") => return None,
+            _ => corpora.push(arg.clone()),
+        }
+    }
+    if corpora.is_empty() {
+        return None;
+    }
+    Some(Args {
+        language: language?,
+        output_dir: output_dir?,
+        min_count,
+        top,
+        corpora,
+    })
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+/// Writes a file by renaming a new one into place, as the bot may have the
+/// old one memory-mapped.
+fn write_output<C: AsRef<[u8]>>(
+    dir: &Path,
+    filename: &str,
+    contents: C,
+) -> Result<(), Box<dyn Error>> {
+    let path = dir.join(filename);
+    let contents = contents.as_ref();
+    write_atomically(&path, contents)?;
+    println!("wrote {} ({} bytes)", path.display(), contents.len());
+    Ok(())
+}
+
+/// What a training run found, for its summary.
+struct Report {
+    tokens: u64,
+    distinct_words: usize,
+    dictionary_words: usize,
+    dictionary_tokens: u64,
+    trigrams: usize,
+}
+
+/// Trains on the corpora and writes the output files.
+fn train(args: &Args) -> Result<Report, Box<dyn Error>> {
+    let mut frequencies: HashMap<String, u64> = HashMap::new();
+    for corpus_filename in &args.corpora {
+        let corpus = std::fs::read_to_string(corpus_filename)?;
+        let mut tokens = 0;
+        for word in corpus_words(&corpus) {
+            *frequencies.entry(word).or_insert(0) += 1;
+            tokens += 1;
+        }
+        println!("{}: {} tokens", corpus_filename, tokens);
+    }
+
+    // Ties are broken alphabetically so that the same corpora always give
+    // byte-identical models.
+    let mut ranked: Vec<(String, u64)> = frequencies.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut model = NgramModel::new();
+    for (word, count) in &ranked {
+        model.add_word(word, (*count).min(u32::MAX as u64) as u32);
+    }
+
+    let kept: Vec<&(String, u64)> = ranked
+        .iter()
+        .filter(|x| x.1 >= args.min_count)
+        .take(args.top.unwrap_or(usize::MAX))
+        .collect();
+
+    let dir = Path::new(&args.output_dir);
+    std::fs::create_dir_all(dir)?;
+    write_output(dir, &format!("{}.ngram", args.language), model.save())?;
+    let words_list: String = ranked
+        .iter()
+        .map(|(word, count)| format!("{}\t{}\n", word, count))
+        .collect();
+    write_output(dir, &format!("{}.words", args.language), &words_list)?;
+    let kept_words: Vec<String> = kept.iter().map(|x| x.0.clone()).collect();
+    write_output(
+        dir,
+        &format!("{}.dict", args.language),
+        front_code(&kept_words),
+    )?;
+    write_output(
+        dir,
+        &format!("{}.fst", args.language),
+        build_fst(&kept_words)?,
+    )?;
+
+    Ok(Report {
+        tokens: ranked.iter().map(|x| x.1).sum(),
+        distinct_words: ranked.len(),
+        dictionary_words: kept.len(),
+        dictionary_tokens: kept.iter().map(|x| x.1).sum(),
+        trigrams: model.trigram_count(),
+    })
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let argv: Vec<_> = std::env::args().collect();
+    let args = match parse_args(&argv) {
+        Some(args) => args,
+        None => {
+            usage(&argv[0]);
+            std::process::exit(1);
+        }
+    };
+
+    let report = train(&args)?;
+    println!("tokens: {}", report.tokens);
+    println!("distinct words: {}", report.distinct_words);
+    println!(
+        "dictionary words: {} ({:.2}% of distinct words)",
+        report.dictionary_words,
+        percent(report.dictionary_words as u64, report.distinct_words as u64)
+    );
+    println!(
+        "token coverage of the dictionary: {:.2}%",
+        percent(report.dictionary_tokens, report.tokens)
+    );
+    println!("distinct trigrams: {}", report.trigrams);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_corpus_gives_the_same_files() {
+        let dir = std::env::temp_dir().join(format!("train-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let corpus = dir.join("corpus.txt");
+        std::fs::write(&corpus, "по-русски мир, мир и мир; м'ясо и мир. Пока!").unwrap();
+        let args = |output: &str| Args {
+            language: String::from("ru"),
+            output_dir: dir.join(output).to_str().unwrap().to_string(),
+            min_count: 2,
+            top: None,
+            corpora: vec![corpus.to_str().unwrap().to_string()],
+        };
+
+        let report = train(&args("first")).unwrap();
+        train(&args("second")).unwrap();
+        assert_eq!(report.tokens, 9);
+        assert_eq!(report.distinct_words, 5);
+        // "мир" and "и" pass --min-count: 6 of the 9 tokens.
+        assert_eq!(report.dictionary_words, 2);
+        assert_eq!(report.dictionary_tokens, 6);
+        for extension in ["ngram", "words", "dict", "fst"] {
+            let filename = format!("ru.{}", extension);
+            let first = std::fs::read(dir.join("first").join(&filename)).unwrap();
+            let second = std::fs::read(dir.join("second").join(&filename)).unwrap();
+            assert_eq!(first, second, "{}", filename);
+        }
+        let words = std::fs::read_to_string(dir.join("first/ru.words")).unwrap();
+        assert_eq!(words, "мир\t4\nи\t2\nм'ясо\t1\nпо-русски\t1\nпока\t1\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
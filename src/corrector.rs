@@ -0,0 +1,748 @@
+use crate::detector::Detector;
+use crate::language::{Language, INTRA_WORD};
+use crate::layout::LayoutPair;
+use crate::settings::Settings;
+use std::iter::FromIterator;
+use std::ops::Range;
+
+const PUNCTUATION: &str = "!\"#$%&\'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+/// Typing on the layout of `from` while meaning to type in `to`.
+pub struct Conversion {
+    pub from: usize,
+    pub to: usize,
+    pub layouts: LayoutPair,
+}
+
+pub struct Corrector {
+    pub languages: Vec<Language>,
+    pub conversions: Vec<Conversion>,
+    pub detector: Box<dyn Detector>,
+    /// Whether converted words that were also misspelled are replaced with
+    /// the word they are a typo of.
+    pub correct_spelling: bool,
+}
+
+impl Corrector {
+    pub fn new(languages: Vec<Language>, detector: Box<dyn Detector>) -> Corrector {
+        let mut conversions = Vec::new();
+        for (from, from_language) in languages.iter().enumerate() {
+            for (to, to_language) in languages.iter().enumerate() {
+                if from_language.is_distinguishable_from(to_language) {
+                    conversions.push(Conversion {
+                        from,
+                        to,
+                        layouts: LayoutPair::new(&from_language.layout, &to_language.layout),
+                    });
+                }
+            }
+        }
+        Corrector {
+            languages,
+            conversions,
+            detector,
+            correct_spelling: false,
+        }
+    }
+
+    /// The best score of `word` as typed, among the languages that could
+    /// have typed it.
+    fn original_score(&self, word: &str) -> f32 {
+        let normalized = normalize(word);
+        self.languages
+            .iter()
+            .filter(|x| x.can_type(word))
+            .map(|x| self.detector.score(&normalized, x))
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    /// Conversions that turn `word` into a recognised word of their target
+    /// language, scoring better than the word as typed.
+    fn candidates(&self, word: &str, original_score: f32) -> Vec<usize> {
+        (0..self.conversions.len())
+            .filter(|i| {
+                let conversion = &self.conversions[*i];
+                if !self.can_apply(*i, word) {
+                    return false;
+                }
+                let score = self.detector.score(
+                    &normalize(&conversion.layouts.convert(word)),
+                    &self.languages[conversion.to],
+                );
+                score > 0.0 && score > original_score
+            })
+            .collect()
+    }
+
+    /// Conversions that turn `word` into a typo of a known word of their
+    /// target language, for words that aren't typos in their own language.
+    fn near_miss_candidates(&self, word: &str) -> Vec<usize> {
+        let normalized = normalize(word);
+        let is_own_typo = self
+            .languages
+            .iter()
+            .any(|x| x.can_type(word) && x.nearest_word(&normalized).is_some());
+        if is_own_typo {
+            return Vec::new();
+        }
+        (0..self.conversions.len())
+            .filter(|i| {
+                let conversion = &self.conversions[*i];
+                self.can_apply(*i, word)
+                    && self.languages[conversion.to]
+                        .nearest_word(&normalize(&conversion.layouts.convert(word)))
+                        .is_some()
+            })
+            .collect()
+    }
+
+    /// Converts `word`, fixing its spelling if that is enabled and the
+    /// converted word is a typo of a known one.
+    fn convert(&self, conversion: usize, word: &str) -> String {
+        let conversion = &self.conversions[conversion];
+        let converted = conversion.layouts.convert(word);
+        let language = &self.languages[conversion.to];
+        let normalized = normalize(&converted);
+        if !self.correct_spelling || language.words.contains(&normalized) {
+            return converted;
+        }
+        let core = converted.trim_matches(|x| PUNCTUATION.contains(x));
+        let correction = match language.nearest_word(&normalized) {
+            Some(correction) if core.to_lowercase() == normalized => correction,
+            _ => return converted,
+        };
+        let start = converted.len()
+            - converted
+                .trim_start_matches(|x| PUNCTUATION.contains(x))
+                .len();
+        let correction = if core.starts_with(char::is_uppercase) {
+            capitalized(correction)
+        } else {
+            String::from(correction)
+        };
+        format!(
+            "{}{}{}",
+            &converted[..start],
+            correction,
+            &converted[start + core.len()..]
+        )
+    }
+
+    fn can_apply(&self, conversion: usize, word: &str) -> bool {
+        let conversion = &self.conversions[conversion];
+        self.languages[conversion.from].can_type(word)
+            && !self.languages[conversion.to].uses_letters_of(word)
+    }
+}
+
+/// What was decided about one token of a message.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+    /// Whitespace, numbers and other tokens without letters.
+    Neutral,
+    /// A link, a mention, code and so on, never converted.
+    Protected,
+    /// Already correct: recognised as typed.
+    Keep,
+    /// Converts into a word the detector recognises, by any of these
+    /// conversions.
+    Known(Vec<usize>),
+    /// Converts into a typo of a known word, by any of these conversions.
+    NearMiss(Vec<usize>),
+    /// A single letter, which converts into some word of nearly every
+    /// language. It proves nothing and only follows its neighbours.
+    SingleLetter,
+    Unknown,
+}
+
+impl Verdict {
+    fn candidates(&self) -> &[usize] {
+        match self {
+            Verdict::Known(candidates) | Verdict::NearMiss(candidates) => candidates,
+            _ => &[],
+        }
+    }
+}
+
+/// One token of a message with the verdict about it and the conversion that
+/// was finally applied, which unknown words adopt from their neighbours.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenDetection {
+    pub text: String,
+    pub verdict: Verdict,
+    pub conversion: Option<usize>,
+}
+
+/// Why a message was or wasn't corrected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reason {
+    /// The message has fewer words or letters than the settings ask for.
+    TooShort,
+    /// None of the words converts into a recognised one.
+    NoKnownWords,
+    /// The recognised words have fewer letters than the settings ask for.
+    NotEnoughEvidence,
+    /// Too few of the words convert into recognised ones.
+    BelowThreshold,
+    Corrected,
+}
+
+/// Everything `detect` decided about a message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Detection {
+    pub tokens: Vec<TokenDetection>,
+    /// Words and their letters, not counting links and code.
+    pub words: usize,
+    pub letters: usize,
+    /// Letters of the words that convert into recognised ones.
+    pub known_letters: usize,
+    /// The share of the letters of words that convert into recognised ones
+    /// among those of words that either convert or are unknown, so that
+    /// longer words weigh more.
+    pub confidence: f32,
+    pub threshold: f32,
+    pub reason: Reason,
+    /// The corrected text, if the message needs correcting.
+    pub corrected: Option<String>,
+}
+
+impl Detection {
+    /// A human-readable account of the decision, one line per word.
+    pub fn explain(&self, corrector: &Corrector) -> String {
+        let conversion_name = |conversion: usize| {
+            let conversion = &corrector.conversions[conversion];
+            format!(
+                "{} -> {}",
+                corrector.languages[conversion.from].code, corrector.languages[conversion.to].code
+            )
+        };
+        let mut out = match &self.reason {
+            Reason::TooShort => format!(
+                "not corrected: too short, {} words and {} letters",
+                self.words, self.letters
+            ),
+            Reason::NoKnownWords => {
+                String::from("not corrected: no word converts into a known one")
+            }
+            Reason::NotEnoughEvidence => format!(
+                "not corrected: known words have only {} letters",
+                self.known_letters
+            ),
+            Reason::BelowThreshold => format!(
+                "not corrected: confidence {:.2} is not above {:.2}",
+                self.confidence, self.threshold
+            ),
+            Reason::Corrected => format!(
+                "corrected {}: confidence {:.2} is above {:.2}",
+                self.tokens
+                    .iter()
+                    .find_map(|x| x.conversion)
+                    .map(conversion_name)
+                    .unwrap_or_default(),
+                self.confidence,
+                self.threshold
+            ),
+        };
+        for token in &self.tokens {
+            let why = match &token.verdict {
+                Verdict::Neutral => continue,
+                Verdict::Protected => String::from("link, mention or code, left alone"),
+                Verdict::Keep => String::from("known as typed"),
+                Verdict::Known(candidates) => format!(
+                    "{} word ({})",
+                    candidates
+                        .iter()
+                        .map(|x| conversion_name(*x))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    corrector.detector.name()
+                ),
+                Verdict::NearMiss(candidates) => format!(
+                    "typo of a {} word",
+                    candidates
+                        .iter()
+                        .map(|x| conversion_name(*x))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Verdict::SingleLetter if token.conversion.is_some() => {
+                    String::from("single letter, follows its neighbours")
+                }
+                Verdict::SingleLetter => String::from("single letter, ignored"),
+                Verdict::Unknown if token.conversion.is_some() => {
+                    String::from("unknown, follows its neighbours")
+                }
+                Verdict::Unknown => String::from("unknown"),
+            };
+            let converted = match (token.conversion, &self.corrected) {
+                (Some(conversion), Some(_)) => {
+                    format!(" -> {}", corrector.convert(conversion, &token.text))
+                }
+                _ => String::new(),
+            };
+            out.push_str(&format!("\n{}{}: {}", token.text, converted, why));
+        }
+        out
+    }
+}
+
+/// Lowercases a word and drops its punctuation, except for apostrophes and
+/// hyphens inside it.
+fn normalize(word: &str) -> String {
+    let word = word.to_lowercase();
+    let core = word.trim_matches(|c| PUNCTUATION.contains(c) || INTRA_WORD.contains(&c));
+    String::from_iter(
+        core.chars()
+            .filter(|c| !PUNCTUATION.contains(*c) || INTRA_WORD.contains(c)),
+    )
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Splits text into alternating runs of whitespace and non-whitespace, so
+/// that joining the pieces gives back the original text. Each protected range
+/// becomes a token of its own, marked with `true`.
+fn split_tokens<'a>(text: &'a str, protected: &[Range<usize>]) -> Vec<(&'a str, bool)> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_whitespace = None;
+    let mut protected = protected.iter().peekable();
+    let mut i = 0;
+    while i < text.len() {
+        if let Some(range) = protected.next_if(|x| x.start == i) {
+            if i > start {
+                tokens.push((&text[start..i], false));
+            }
+            tokens.push((&text[range.clone()], true));
+            i = range.end;
+            start = i;
+            in_whitespace = None;
+            continue;
+        }
+        let c = text[i..].chars().next().unwrap();
+        if in_whitespace != Some(c.is_whitespace()) {
+            if i > start {
+                tokens.push((&text[start..i], false));
+            }
+            start = i;
+            in_whitespace = Some(c.is_whitespace());
+        }
+        i += c.len_utf8();
+    }
+    if start < text.len() {
+        tokens.push((&text[start..], false));
+    }
+    tokens
+}
+
+/// Decides for every word of `text` whether it was typed on the wrong layout
+/// and whether the message as a whole needs correcting. Protected byte
+/// ranges, such as links and code, are never converted.
+///
+/// Words that convert into a known word pick the conversion most of the
+/// message agrees on. Unknown words between them follow their neighbours,
+/// while known words and links split the message into independent runs.
+pub fn detect(
+    text: &str,
+    protected: &[Range<usize>],
+    corrector: &Corrector,
+    settings: &Settings,
+) -> Detection {
+    let (tokens, is_protected): (Vec<&str>, Vec<bool>) =
+        split_tokens(text, protected).into_iter().unzip();
+    let verdicts: Vec<Verdict> = tokens
+        .iter()
+        .zip(&is_protected)
+        .map(|(token, is_protected)| {
+            if *is_protected {
+                Verdict::Protected
+            } else if !token.contains(char::is_alphabetic) {
+                Verdict::Neutral
+            } else if token.chars().filter(|x| x.is_alphabetic()).count() == 1 {
+                Verdict::SingleLetter
+            } else {
+                let original_score = corrector.original_score(token);
+                match corrector.candidates(token, original_score) {
+                    candidates if !candidates.is_empty() => Verdict::Known(candidates),
+                    _ if original_score > 0.0 => Verdict::Keep,
+                    _ => match corrector.near_miss_candidates(token) {
+                        candidates if !candidates.is_empty() => Verdict::NearMiss(candidates),
+                        _ => Verdict::Unknown,
+                    },
+                }
+            }
+        })
+        .collect();
+
+    let mut votes = vec![0; corrector.conversions.len()];
+    for verdict in &verdicts {
+        for candidate in verdict.candidates() {
+            votes[*candidate] += 1;
+        }
+    }
+    let mut chosen: Vec<Option<usize>> = verdicts
+        .iter()
+        .map(|verdict| {
+            verdict
+                .candidates()
+                .iter()
+                .copied()
+                .fold(None, |best: Option<usize>, x| match best {
+                    Some(best) if votes[best] >= votes[x] => Some(best),
+                    _ => Some(x),
+                })
+        })
+        .collect();
+
+    let (words, letters) = tokens
+        .iter()
+        .zip(&verdicts)
+        .filter(|(_, verdict)| !matches!(verdict, Verdict::Neutral | Verdict::Protected))
+        .fold((0, 0), |(words, letters), (token, _)| {
+            (
+                words + 1,
+                letters + token.chars().filter(|x| x.is_alphabetic()).count(),
+            )
+        });
+    let letters_where = |selected: &dyn Fn(usize) -> bool| -> usize {
+        (0..tokens.len())
+            .filter(|i| selected(*i))
+            .map(|i| tokens[i].chars().filter(|x| x.is_alphabetic()).count())
+            .sum()
+    };
+    let known_letters = letters_where(&|i| chosen[i].is_some());
+    let unknown_letters = letters_where(&|i| verdicts[i] == Verdict::Unknown);
+    let confidence = if known_letters == 0 {
+        0.0
+    } else {
+        known_letters as f32 / (known_letters + unknown_letters) as f32
+    };
+    let reason = if words < settings.min_words || letters < settings.min_length {
+        Reason::TooShort
+    } else if known_letters == 0 {
+        Reason::NoKnownWords
+    } else if known_letters < settings.min_known_letters {
+        Reason::NotEnoughEvidence
+    } else if confidence <= settings.threshold {
+        Reason::BelowThreshold
+    } else {
+        Reason::Corrected
+    };
+
+    let corrected = if reason == Reason::Corrected {
+        for i in 0..tokens.len() {
+            if let Verdict::Unknown | Verdict::SingleLetter = verdicts[i] {
+                let neighbour = nearest_known(&verdicts, &chosen, (0..i).rev())
+                    .or_else(|| nearest_known(&verdicts, &chosen, i + 1..tokens.len()));
+                chosen[i] = neighbour.filter(|x| corrector.can_apply(*x, tokens[i]));
+            }
+        }
+        Some(String::from_iter(tokens.iter().zip(&chosen).map(
+            |(token, conversion)| match conversion {
+                Some(conversion) => corrector.convert(*conversion, token),
+                None => String::from(*token),
+            },
+        )))
+    } else {
+        chosen.iter_mut().for_each(|x| *x = None);
+        None
+    };
+
+    Detection {
+        tokens: tokens
+            .into_iter()
+            .zip(verdicts)
+            .zip(chosen)
+            .map(|((text, verdict), conversion)| TokenDetection {
+                text: String::from(text),
+                verdict,
+                conversion,
+            })
+            .collect(),
+        words,
+        letters,
+        known_letters,
+        confidence,
+        threshold: settings.threshold,
+        reason,
+        corrected,
+    }
+}
+
+/// The text with just the words typed on the wrong layout converted, or
+/// `None` when the message doesn't need correcting.
+pub fn correct_text(
+    text: &str,
+    protected: &[Range<usize>],
+    corrector: &Corrector,
+    settings: &Settings,
+) -> Option<String> {
+    detect(text, protected, corrector, settings).corrected
+}
+
+fn nearest_known<I: Iterator<Item = usize>>(
+    verdicts: &[Verdict],
+    chosen: &[Option<usize>],
+    range: I,
+) -> Option<usize> {
+    for i in range {
+        match verdicts[i] {
+            Verdict::Keep | Verdict::Protected => return None,
+            Verdict::Known(_) | Verdict::NearMiss(_) => return chosen[i],
+            Verdict::Neutral | Verdict::SingleLetter | Verdict::Unknown => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detector::{CombinedDetector, DictionaryDetector};
+    use crate::dictionary::FstDictionary;
+    use crate::entities::protected_ranges;
+    use crate::language;
+    use crate::ngram::{corpus_words, NgramDetector, NgramModel};
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    fn language(code: &str, words: &[&str]) -> Language {
+        Language::new(
+            code,
+            language::default_layout(code).unwrap(),
+            Box::new(
+                words
+                    .iter()
+                    .map(|x| String::from(*x))
+                    .collect::<HashSet<_>>(),
+            ),
+        )
+    }
+
+    fn correct(text: &str, corrector: &Corrector) -> Option<String> {
+        correct_text(
+            text,
+            &protected_ranges(text, &[]),
+            corrector,
+            &Settings::default(),
+        )
+    }
+
+    fn corrector() -> Corrector {
+        Corrector::new(
+            vec![
+                language("en", &["hello", "world", "check", "this"]),
+                language(
+                    "ru",
+                    &[
+                        "привет",
+                        "мир",
+                        "прикольная",
+                        "ссылка",
+                        "как",
+                        "дела",
+                        "смотри",
+                        "красиво",
+                        "я",
+                        "ты",
+                    ],
+                ),
+                language("uk", &["привіт", "світ"]),
+            ],
+            Box::new(DictionaryDetector),
+        )
+    }
+
+    #[test]
+    fn picks_best_matching_language() {
+        let corrector = corrector();
+        assert_eq!(correct("ghbdtn vbh", &corrector).unwrap(), "привет мир");
+        assert_eq!(correct("ghbdsn cdsn", &corrector).unwrap(), "привіт світ");
+        assert_eq!(correct("руддщ цщкдв", &corrector).unwrap(), "hello world");
+        assert_eq!(correct("привіт світ", &corrector), None);
+    }
+
+    #[test]
+    fn converts_only_mistyped_words() {
+        let corrector = corrector();
+        assert_eq!(
+            correct("check this ghbrjkmyfz ccskrf", &corrector).unwrap(),
+            "check this прикольная ссылка"
+        );
+        assert_eq!(
+            correct("https://example.com/a,b  rfr ltkf?", &corrector).unwrap(),
+            "https://example.com/a,b  как дела,"
+        );
+        assert_eq!(correct("hello world", &corrector), None);
+        assert_eq!(
+            correct("ghbdtn @vbh #vbh `vbh` vbh", &corrector).unwrap(),
+            "привет @vbh #vbh `vbh` мир"
+        );
+    }
+
+    #[test]
+    fn corrects_captions_keeping_their_links() {
+        let corrector = corrector();
+        let update: crate::telegram::Update =
+            serde_json::from_str(include_str!("../tests/fixtures/updates/photo.json")).unwrap();
+        let message = update.message.unwrap();
+        let caption = message.text_or_caption().unwrap();
+        let protected = protected_ranges(caption, message.text_or_caption_entities());
+        assert_eq!(
+            correct_text(caption, &protected, &corrector, &Settings::default()).unwrap(),
+            "смотри как красиво t.me/test"
+        );
+    }
+
+    fn ngram_detector() -> NgramDetector {
+        let mut models = HashMap::new();
+        models.insert(
+            String::from("en"),
+            NgramModel::train("hello world, see you tomorrow at the office"),
+        );
+        models.insert(
+            String::from("ru"),
+            NgramModel::train("привет мир, как дела? увидимся завтра в офисе"),
+        );
+        NgramDetector::new(models, NgramDetector::DEFAULT_MARGIN)
+    }
+
+    #[test]
+    fn ngram_detectors_recognise_only_likely_words() {
+        let ngram = Corrector::new(
+            vec![language("en", &[]), language("ru", &[])],
+            Box::new(ngram_detector()),
+        );
+        let combined = Corrector::new(
+            vec![
+                language("en", &["hello"]),
+                language("ru", &["привет", "мир"]),
+            ],
+            Box::new(CombinedDetector::new(vec![
+                (1.0, Box::new(DictionaryDetector)),
+                (1.0, Box::new(ngram_detector())),
+            ])),
+        );
+        for corrector in [&ngram, &combined] {
+            assert_eq!(correct("ghbdtn vbh", corrector).unwrap(), "привет мир");
+            assert_eq!(correct("hello world", corrector), None);
+            assert_eq!(correct("see you tomorrow at the office", corrector), None);
+            // Gibberish is recognised neither as typed nor converted.
+            let detection = detect("qwerty zxcv", &[], corrector, &Settings::default());
+            assert!(detection
+                .tokens
+                .iter()
+                .all(|x| !matches!(x.verdict, Verdict::Keep | Verdict::Known(_))));
+        }
+    }
+
+    #[test]
+    fn recognises_trained_words_with_apostrophes_and_hyphens() {
+        let trained = |code: &str, corpus: &str| {
+            let words: Vec<String> = corpus_words(corpus).collect();
+            Language::new(
+                code,
+                language::default_layout(code).unwrap(),
+                Box::new(FstDictionary::from_words(&words).unwrap()),
+            )
+        };
+        let corrector = Corrector::new(
+            vec![
+                language("en", &["hello"]),
+                trained("ru", "Говорить по-русски? Привет!"),
+                trained("uk", "М'ясо та молоко."),
+            ],
+            Box::new(DictionaryDetector),
+        );
+        assert_eq!(
+            correct("gj-heccrb ghbdtn", &corrector).unwrap(),
+            "по-русски привет"
+        );
+        // The Ukrainian apostrophe is on the key of the grave accent.
+        assert_eq!(
+            correct("v`zcj nf vjkjrj", &corrector).unwrap(),
+            "м'ясо та молоко"
+        );
+    }
+
+    #[test]
+    fn unknown_words_follow_their_neighbours() {
+        let corrector = corrector();
+        assert_eq!(
+            correct("ghbdtn vbh rjkktuf", &corrector).unwrap(),
+            "привет мир коллега"
+        );
+        assert_eq!(correct("ghbdtn rjkktuf", &corrector), None);
+    }
+
+    #[test]
+    fn needs_enough_evidence() {
+        let corrector = corrector();
+        assert_eq!(correct("z", &corrector), None);
+        assert_eq!(correct("ns", &corrector), None);
+        assert_eq!(correct("z ns", &corrector), None);
+        assert_eq!(correct("ghbdtn z", &corrector).unwrap(), "привет я");
+        assert_eq!(correct("vbh xnj-nj ytgjyznyjt", &corrector), None);
+    }
+
+    #[test]
+    fn detection_explains_the_decision() {
+        let corrector = corrector();
+        let detection = detect(
+            "ghbdtn hello rjkktuf",
+            &[],
+            &corrector,
+            &Settings::default(),
+        );
+        assert_eq!(detection.reason, Reason::BelowThreshold);
+        assert_eq!(detection.confidence, 6.0 / 13.0);
+        let verdicts: Vec<&Verdict> = detection.tokens.iter().map(|x| &x.verdict).collect();
+        assert!(matches!(verdicts[0], Verdict::Known(_)));
+        assert_eq!(verdicts[2], &Verdict::Keep);
+        assert_eq!(verdicts[4], &Verdict::Unknown);
+
+        let detection = detect("ghbdtn vbh rjkktuf", &[], &corrector, &Settings::default());
+        assert_eq!(detection.reason, Reason::Corrected);
+        assert_eq!(
+            detection.explain(&corrector),
+            "corrected en -> ru: confidence 0.56 is above 0.50\n\
+             ghbdtn -> привет: en -> ru word (dictionary)\n\
+             vbh -> мир: en -> ru word (dictionary)\n\
+             rjkktuf -> коллега: unknown, follows its neighbours"
+        );
+
+        let settings = Settings {
+            threshold: 0.7,
+            ..Settings::default()
+        };
+        let detection = detect("ghbdtn vbh rjkktuf", &[], &corrector, &settings);
+        assert_eq!(detection.reason, Reason::BelowThreshold);
+        let settings = Settings {
+            min_words: 4,
+            ..Settings::default()
+        };
+        let detection = detect("ghbdtn vbh rjkktuf", &[], &corrector, &settings);
+        assert_eq!(detection.reason, Reason::TooShort);
+        assert_eq!((detection.words, detection.letters), (3, 16));
+    }
+
+    #[test]
+    fn near_misses_count_as_known() {
+        let mut corrector = corrector();
+        assert_eq!(correct("Ghbdtyn vbh", &corrector), None);
+        for language in &mut corrector.languages {
+            let words = language.words.words_with_prefix("", usize::MAX);
+            language.index_typos(words, 1);
+        }
+        assert_eq!(correct("Ghbdtyn vbh", &corrector).unwrap(), "Привент мир");
+        assert_eq!(correct("hellp world", &corrector), None);
+        corrector.correct_spelling = true;
+        assert_eq!(correct("Ghbdtyn vbh!", &corrector).unwrap(), "Привет мир!");
+    }
+}
@@ -0,0 +1,56 @@
+use crate::language::Language;
+
+/// Tells how much a word looks like a word of some language.
+///
+/// Words are passed lowercased and without punctuation. A score above zero
+/// means the word is recognised; otherwise scores are only meaningful when
+/// compared with other scores of the same detector, which is how the original
+/// and the converted spelling of a word are weighed against each other.
+pub trait Detector {
+    fn score(&self, word: &str, language: &Language) -> f32;
+
+    /// What recognised the word, for explaining decisions.
+    fn name(&self) -> String;
+}
+
+/// Recognises exactly the words of the language's word list.
+pub struct DictionaryDetector;
+
+impl Detector for DictionaryDetector {
+    fn score(&self, word: &str, language: &Language) -> f32 {
+        if language.words.contains(word) {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+
+    fn name(&self) -> String {
+        String::from("dictionary")
+    }
+}
+
+/// Adds up the weighted scores of several detectors.
+pub struct CombinedDetector {
+    detectors: Vec<(f32, Box<dyn Detector>)>,
+}
+
+impl CombinedDetector {
+    pub fn new(detectors: Vec<(f32, Box<dyn Detector>)>) -> CombinedDetector {
+        CombinedDetector { detectors }
+    }
+}
+
+impl Detector for CombinedDetector {
+    fn score(&self, word: &str, language: &Language) -> f32 {
+        self.detectors
+            .iter()
+            .map(|(weight, detector)| weight * detector.score(word, language))
+            .sum()
+    }
+
+    fn name(&self) -> String {
+        let names: Vec<String> = self.detectors.iter().map(|x| x.1.name()).collect();
+        names.join("+")
+    }
+}
@@ -0,0 +1,234 @@
+use crate::hunspell::HunspellDictionary;
+use crate::morphology::MorphologyDictionary;
+use fst::automaton::{Automaton, Str};
+use fst::{IntoStreamer, Set, Streamer};
+use memmap2::Mmap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+
+// First line of a front-coded dictionary file.
+const DICT_HEADER: &str = "# layout-corrector dictionary v1";
+
+/// A set of words, answering membership and prefix queries.
+pub trait Dictionary {
+    fn contains(&self, word: &str) -> bool;
+
+    /// Up to `limit` words starting with `prefix`, in lexicographic order.
+    fn words_with_prefix(&self, prefix: &str, limit: usize) -> Vec<String>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Dictionary for HashSet<String> {
+    fn contains(&self, word: &str) -> bool {
+        HashSet::contains(self, word)
+    }
+
+    fn words_with_prefix(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let mut words: Vec<String> = self
+            .iter()
+            .filter(|x| x.starts_with(prefix))
+            .cloned()
+            .collect();
+        words.sort();
+        words.truncate(limit);
+        words
+    }
+
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+}
+
+/// A dictionary stored as a finite state transducer, which shares both the
+/// prefixes and the suffixes of word forms. Opened from a file it is
+/// memory-mapped, so only the pages lookups touch are ever loaded.
+pub struct FstDictionary<D = Mmap> {
+    set: Set<D>,
+}
+
+impl FstDictionary<Mmap> {
+    pub fn open(filename: &str) -> Result<FstDictionary<Mmap>, Box<dyn Error>> {
+        let file = File::open(filename)?;
+        // The trainer never modifies the file in place: it renames a new
+        // file over it, which leaves this mapping on the old one.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(FstDictionary {
+            set: Set::new(mmap)?,
+        })
+    }
+}
+
+impl FstDictionary<Vec<u8>> {
+    pub fn from_words(words: &[String]) -> Result<FstDictionary<Vec<u8>>, Box<dyn Error>> {
+        Ok(FstDictionary {
+            set: Set::new(build_fst(words)?)?,
+        })
+    }
+}
+
+impl<D: AsRef<[u8]>> Dictionary for FstDictionary<D> {
+    fn contains(&self, word: &str) -> bool {
+        self.set.contains(word)
+    }
+
+    fn words_with_prefix(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let mut stream = self
+            .set
+            .search(Str::new(prefix).starts_with())
+            .into_stream();
+        let mut words = Vec::new();
+        while let Some(word) = stream.next() {
+            if words.len() == limit {
+                break;
+            }
+            words.push(String::from_utf8_lossy(word).into_owned());
+        }
+        words
+    }
+
+    fn len(&self) -> usize {
+        self.set.len()
+    }
+}
+
+/// Serializes words into the FST format `FstDictionary::open` reads.
+pub fn build_fst(words: &[String]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut sorted: Vec<&String> = words.iter().collect();
+    sorted.sort();
+    sorted.dedup();
+    Ok(Set::from_iter(sorted)?.into_fst().into_inner())
+}
+
+/// Opens a dictionary file: an `.fst` file is memory-mapped, a `.morph` file
+/// is a morphology of lemmas and paradigms, a `.dic` file is a Hunspell
+/// dictionary with its `.aff` file next to it, anything else is read by
+/// `build_words`.
+pub fn load_dictionary(filename: &str) -> Result<Box<dyn Dictionary>, Box<dyn Error>> {
+    if filename.ends_with(".fst") {
+        Ok(Box::new(FstDictionary::open(filename)?))
+    } else if filename.ends_with(".dic") {
+        Ok(Box::new(HunspellDictionary::load(filename)?))
+    } else if filename.ends_with(".morph") {
+        Ok(Box::new(MorphologyDictionary::load(filename)?))
+    } else {
+        Ok(Box::new(build_words(filename)?))
+    }
+}
+
+/// Reads a word list: either one word per line, optionally followed by a tab
+/// and its frequency as the trainer writes them, or a front-coded dictionary.
+pub fn build_words(filename: &str) -> Result<HashSet<String>, Box<dyn Error>> {
+    let mut file = File::open(filename)?;
+    let mut buf = String::new();
+    file.read_to_string(&mut buf)?;
+    if buf.starts_with(DICT_HEADER) {
+        return Ok(front_decode(&buf)?.into_iter().collect());
+    }
+    Ok(buf
+        .split('\n')
+        .filter_map(|s| s.split('\t').next())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Reads the first `limit` words of a list ranked by frequency, such as the
+/// trainer's .words file.
+pub fn ranked_words(filename: &str, limit: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(std::fs::read_to_string(filename)?
+        .lines()
+        .filter_map(|s| s.split('\t').next())
+        .filter(|s| !s.is_empty())
+        .take(limit)
+        .map(String::from)
+        .collect())
+}
+
+/// Stores sorted words as the length of the prefix shared with the previous
+/// word and the rest of the word, which is a fraction of the plain list for
+/// the long runs of word forms a full dictionary consists of.
+pub fn front_code(words: &[String]) -> String {
+    let mut sorted: Vec<&String> = words.iter().collect();
+    sorted.sort();
+    sorted.dedup();
+    let mut out = String::from(DICT_HEADER);
+    out.push('\n');
+    let mut previous: &str = "";
+    for word in sorted {
+        let shared = previous
+            .chars()
+            .zip(word.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix: String = word.chars().skip(shared).collect();
+        out.push_str(&format!("{}\t{}\n", shared, suffix));
+        previous = word;
+    }
+    out
+}
+
+pub fn front_decode(src: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut words = Vec::new();
+    let mut previous = String::new();
+    for (line_number, line) in src.lines().enumerate().skip(1) {
+        let mut fields = line.splitn(2, '\t');
+        let shared: usize = fields
+            .next()
+            .unwrap_or("")
+            .parse()
+            .map_err(|_| format!("dictionary line {}: bad prefix length", line_number + 1))?;
+        let mut word: String = previous.chars().take(shared).collect();
+        word.push_str(fields.next().unwrap_or(""));
+        words.push(word.clone());
+        previous = word;
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fst_answers_like_a_hash_set() {
+        let words: Vec<String> = ["привет", "приветик", "привал", "мир"]
+            .iter()
+            .map(|x| String::from(*x))
+            .collect();
+        let set: HashSet<String> = words.iter().cloned().collect();
+        let fst = FstDictionary::from_words(&words).unwrap();
+        for word in &["привет", "прив", "мир", "миры"] {
+            assert_eq!(
+                Dictionary::contains(&fst, word),
+                Dictionary::contains(&set, word)
+            );
+        }
+        assert_eq!(
+            fst.words_with_prefix("прив", 10),
+            set.words_with_prefix("прив", 10)
+        );
+        assert_eq!(fst.words_with_prefix("привет", 1), vec!["привет"]);
+        assert_eq!(fst.len(), 4);
+    }
+
+    #[test]
+    fn front_coding_round_trips() {
+        let words: Vec<String> = ["привет", "приветик", "привал", "мир", "мир"]
+            .iter()
+            .map(|x| String::from(*x))
+            .collect();
+        let coded = front_code(&words);
+        assert!(coded.contains("\n4\tет\n6\tик\n"));
+        assert_eq!(
+            front_decode(&coded).unwrap(),
+            vec!["мир", "привал", "привет", "приветик"]
+        );
+    }
+}
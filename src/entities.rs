@@ -0,0 +1,121 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+// Entity types whose text must reach the chat exactly as it was typed.
+const PRESERVED_ENTITIES: &[&str] = &[
+    "url",
+    "text_link",
+    "mention",
+    "text_mention",
+    "hashtag",
+    "cashtag",
+    "bot_command",
+    "email",
+    "code",
+    "pre",
+];
+
+// Used when Telegram didn't mark up the message, e.g. for links it doesn't
+// recognise or text that never went through the Bot API.
+const FALLBACK_PATTERN: &str = r"(?x)
+    ```[\s\S]*?```                      # code block
+    | `[^`\n]+`                         # inline code
+    | (?:[a-zA-Z][a-zA-Z0-9+.-]*://|www\.)\S+   # url
+    | [\w.+-]+@[\w-]+(?:\.[\w-]+)+      # email
+    | [@\#]\w+                          # mention or hashtag
+";
+
+/// A `MessageEntity` of the Bot API. Offsets and lengths are in UTF-16 code
+/// units.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MessageEntity {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+fn fallback_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(FALLBACK_PATTERN).unwrap())
+}
+
+/// Converts a UTF-16 range into a byte range of `text`, clamped to its end.
+fn utf16_to_byte_range(text: &str, offset: usize, length: usize) -> Range<usize> {
+    let mut utf16_position = 0;
+    let (mut start, mut end) = (text.len(), text.len());
+    for (i, c) in text.char_indices() {
+        if utf16_position == offset {
+            start = i;
+        }
+        if utf16_position == offset + length {
+            end = i;
+            break;
+        }
+        utf16_position += c.len_utf16();
+    }
+    start..end.max(start)
+}
+
+/// Byte ranges of `text` that must not be converted: the preserved entities
+/// Telegram reported plus whatever the fallback tokenizer finds. The ranges
+/// are sorted and don't overlap.
+pub fn protected_ranges(text: &str, entities: &[MessageEntity]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = entities
+        .iter()
+        .filter(|x| PRESERVED_ENTITIES.contains(&x.kind.as_str()))
+        .map(|x| utf16_to_byte_range(text, x.offset, x.length))
+        .chain(fallback_pattern().find_iter(text).map(|x| x.range()))
+        .filter(|x| !x.is_empty())
+        .collect();
+    ranges.sort_by_key(|x| x.start);
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protected<'a>(text: &'a str, entities: &[MessageEntity]) -> Vec<&'a str> {
+        protected_ranges(text, entities)
+            .into_iter()
+            .map(|x| &text[x])
+            .collect()
+    }
+
+    #[test]
+    fn finds_spans_without_entities() {
+        assert_eq!(
+            protected(
+                "ckfq https://example.com/a,b @username #nfu `rjl` vfvf@example.com",
+                &[]
+            ),
+            vec![
+                "https://example.com/a,b",
+                "@username",
+                "#nfu",
+                "`rjl`",
+                "vfvf@example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_utf16_entity_offsets() {
+        let entities = [MessageEntity {
+            kind: String::from("code"),
+            offset: 8,
+            length: 3,
+        }];
+        assert_eq!(protected("😀 ghbd rjl", &entities), vec!["rjl"]);
+    }
+}
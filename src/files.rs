@@ -0,0 +1,12 @@
+use std::error::Error;
+use std::path::Path;
+
+/// Writes a temporary file next to `path` and renames it over the old one,
+/// so a crash never leaves a half-written file behind.
+pub fn write_atomically<C: AsRef<[u8]>>(path: &Path, contents: C) -> Result<(), Box<dyn Error>> {
+    let mut temporary = path.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+
+// Shorter words are never matched fuzzily: one edit away from almost any
+// short word there is another word.
+const MIN_FUZZY_LENGTH: usize = 5;
+
+// Words at least this long may be up to the index's full distance away,
+// shorter ones only one edit.
+const LONG_WORD_LENGTH: usize = 9;
+
+/// Finds dictionary words within a small edit distance of a misspelled one,
+/// SymSpell style: every word is indexed under all the strings left after
+/// deleting up to `max_distance` of its characters, so a lookup only has to
+/// generate the deletes of the misspelled word and verify what they hit.
+pub struct FuzzyIndex {
+    words: Vec<String>,
+    deletes: HashMap<String, Vec<u32>>,
+    max_distance: usize,
+}
+
+/// Every string left after deleting up to `distance` characters of `word`,
+/// `word` itself included.
+fn deletes(word: &str, distance: usize) -> HashSet<String> {
+    let mut all = HashSet::from([String::from(word)]);
+    let mut last = vec![word.chars().collect::<Vec<char>>()];
+    for _ in 0..distance {
+        let mut next = Vec::new();
+        for chars in &last {
+            for i in 0..chars.len() {
+                let mut shorter = chars.clone();
+                shorter.remove(i);
+                if all.insert(shorter.iter().collect()) {
+                    next.push(shorter);
+                }
+            }
+        }
+        last = next;
+    }
+    all
+}
+
+/// Optimal string alignment distance: insertions, deletions, substitutions
+/// and transpositions of adjacent characters all cost one edit.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+impl FuzzyIndex {
+    pub fn new(words: Vec<String>, max_distance: usize) -> FuzzyIndex {
+        let mut deletes_index: HashMap<String, Vec<u32>> = HashMap::new();
+        for (id, word) in words.iter().enumerate() {
+            if word.chars().count() < MIN_FUZZY_LENGTH.saturating_sub(max_distance) {
+                continue;
+            }
+            for delete in deletes(word, max_distance) {
+                deletes_index.entry(delete).or_default().push(id as u32);
+            }
+        }
+        FuzzyIndex {
+            words,
+            deletes: deletes_index,
+            max_distance,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// How many edits away from a word of `length` characters a match may be.
+    fn allowed_distance(&self, length: usize) -> usize {
+        if length < MIN_FUZZY_LENGTH {
+            0
+        } else if length < LONG_WORD_LENGTH {
+            self.max_distance.min(1)
+        } else {
+            self.max_distance
+        }
+    }
+
+    /// The closest indexed word to `word`, other than `word` itself, if one
+    /// is close enough. Ties go to the word indexed first.
+    pub fn nearest(&self, word: &str) -> Option<&str> {
+        let distance = self.allowed_distance(word.chars().count());
+        if distance == 0 {
+            return None;
+        }
+        let mut best: Option<(usize, u32)> = None;
+        for delete in deletes(word, distance) {
+            for id in self.deletes.get(&delete).into_iter().flatten() {
+                let candidate = &self.words[*id as usize];
+                let candidate_distance = edit_distance(word, candidate);
+                if candidate_distance == 0 || candidate_distance > distance {
+                    continue;
+                }
+                if best.map(|x| (candidate_distance, *id) < x).unwrap_or(true) {
+                    best = Some((candidate_distance, *id));
+                }
+            }
+        }
+        best.map(|(_, id)| self.words[id as usize].as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_near_misses() {
+        let words = ["привет", "приветик", "кот", "собака", "профессионал"];
+        let index = FuzzyIndex::new(words.iter().map(|x| String::from(*x)).collect(), 2);
+        assert_eq!(index.nearest("привент"), Some("привет"));
+        assert_eq!(index.nearest("приевт"), Some("привет"));
+        assert_eq!(index.nearest("прафесионал"), Some("профессионал"));
+        assert_eq!(index.nearest("собакаа"), Some("собака"));
+        assert_eq!(index.nearest("кит"), None);
+        assert_eq!(index.nearest("привет"), None);
+        assert_eq!(index.nearest("прнвенн"), None);
+        assert_eq!(edit_distance("привет", "пиврет"), 2);
+    }
+}
@@ -0,0 +1,552 @@
+use crate::dictionary::Dictionary;
+use encoding_rs::{Encoding, UTF_8};
+use std::collections::HashMap;
+use std::error::Error;
+
+// Written for an empty strip or affix in affix rules.
+const EMPTY_AFFIX: &str = "0";
+
+type Flag = u32;
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlagType {
+    // One character per flag, the default.
+    Char,
+    // Two characters per flag.
+    Long,
+    // Comma-separated decimal numbers.
+    Num,
+}
+
+enum CharClass {
+    Any,
+    OneOf(Vec<char>),
+    NoneOf(Vec<char>),
+}
+
+impl CharClass {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharClass::Any => true,
+            CharClass::OneOf(chars) => chars.contains(&c),
+            CharClass::NoneOf(chars) => !chars.contains(&c),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum AffixKind {
+    Prefix,
+    Suffix,
+}
+
+/// One PFX or SFX rule: `strip` is removed from the stem and `affix` added
+/// in its place, if the stem matches `condition`.
+struct Affix {
+    kind: AffixKind,
+    flag: Flag,
+    cross_product: bool,
+    strip: String,
+    affix: String,
+    condition: Vec<CharClass>,
+    // Flags of suffixes that may be added on top of this one.
+    continuation: Vec<Flag>,
+}
+
+impl Affix {
+    fn applies_to(&self, stem: &str) -> bool {
+        let mut chars: Box<dyn Iterator<Item = char>> = match self.kind {
+            AffixKind::Prefix => Box::new(stem.chars()),
+            AffixKind::Suffix => Box::new(stem.chars().rev()),
+        };
+        let mut condition: Box<dyn Iterator<Item = &CharClass>> = match self.kind {
+            AffixKind::Prefix => Box::new(self.condition.iter()),
+            AffixKind::Suffix => Box::new(self.condition.iter().rev()),
+        };
+        condition.all(|class| chars.next().map(|c| class.matches(c)).unwrap_or(false))
+    }
+
+    /// `stem` with this affix applied, if it can be.
+    fn apply(&self, stem: &str) -> Option<String> {
+        if !self.applies_to(stem) {
+            return None;
+        }
+        match self.kind {
+            AffixKind::Prefix => stem
+                .strip_prefix(self.strip.as_str())
+                .map(|rest| format!("{}{}", self.affix, rest)),
+            AffixKind::Suffix => stem
+                .strip_suffix(self.strip.as_str())
+                .map(|rest| format!("{}{}", rest, self.affix)),
+        }
+    }
+}
+
+fn parse_condition(condition: &str) -> Result<Vec<CharClass>, Box<dyn Error>> {
+    let mut classes = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        classes.push(match c {
+            '.' => CharClass::Any,
+            '[' => {
+                let mut set = Vec::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => set.push(c),
+                        None => return Err(format!("unclosed '[' in '{}'", condition).into()),
+                    }
+                }
+                if set.first() == Some(&'^') {
+                    CharClass::NoneOf(set.split_off(1))
+                } else {
+                    CharClass::OneOf(set)
+                }
+            }
+            c => CharClass::OneOf(vec![c]),
+        });
+    }
+    Ok(classes)
+}
+
+fn parse_affix_text(text: &str) -> String {
+    if text == EMPTY_AFFIX {
+        String::new()
+    } else {
+        String::from(text)
+    }
+}
+
+/// Decodes an `.aff` or `.dic` file in the encoding the `.aff` file's `SET`
+/// line names, UTF-8 if there is none.
+fn decode(bytes: &[u8], aff: &[u8]) -> String {
+    let encoding = aff
+        .split(|x| *x == b'\n')
+        .find_map(|line| line.strip_prefix(b"SET "))
+        .and_then(|label| Encoding::for_label(String::from_utf8_lossy(label).trim().as_bytes()))
+        .unwrap_or(UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// A Hunspell `.dic` word list together with the prefix and suffix rules of
+/// its `.aff` file, which is how the LibreOffice dictionaries are
+/// distributed.
+pub struct HunspellDictionary {
+    // A stem can be listed more than once with different flags.
+    stems: HashMap<String, Vec<Vec<Flag>>>,
+    affixes: Vec<Affix>,
+    // Affixes by the text they add, for analysis.
+    by_text: HashMap<(AffixKind, String), Vec<usize>>,
+    // Affixes by their flag, for generating word forms.
+    by_flag: HashMap<Flag, Vec<usize>>,
+    longest_affix: usize,
+    need_affix: Option<Flag>,
+    forbidden: Option<Flag>,
+}
+
+struct FlagParser {
+    flag_type: FlagType,
+    // AF lines: numbers standing for whole sets of flags.
+    aliases: Vec<Vec<Flag>>,
+}
+
+impl FlagParser {
+    fn parse(&self, flags: &str) -> Result<Vec<Flag>, Box<dyn Error>> {
+        if !self.aliases.is_empty() {
+            if let Ok(alias) = flags.parse::<usize>() {
+                return match alias.checked_sub(1).and_then(|x| self.aliases.get(x)) {
+                    Some(flags) => Ok(flags.clone()),
+                    None => Err(format!("unknown flag alias {}", alias).into()),
+                };
+            }
+        }
+        self.parse_flags(flags)
+    }
+
+    fn parse_flags(&self, flags: &str) -> Result<Vec<Flag>, Box<dyn Error>> {
+        match self.flag_type {
+            FlagType::Char => Ok(flags.chars().map(|x| x as Flag).collect()),
+            FlagType::Long => {
+                let chars: Vec<char> = flags.chars().collect();
+                if !chars.len().is_multiple_of(2) {
+                    return Err(
+                        format!("odd number of characters in long flags '{}'", flags).into(),
+                    );
+                }
+                Ok(chars
+                    .chunks(2)
+                    .map(|x| (x[0] as Flag) << 16 | x[1] as Flag)
+                    .collect())
+            }
+            FlagType::Num => flags
+                .split(',')
+                .map(|x| {
+                    x.parse()
+                        .map_err(|_| format!("bad numeric flag '{}'", x).into())
+                })
+                .collect(),
+        }
+    }
+
+    fn parse_one(&self, flag: &str) -> Result<Flag, Box<dyn Error>> {
+        match self.parse_flags(flag)?.as_slice() {
+            [flag] => Ok(*flag),
+            _ => Err(format!("'{}' is not a single flag", flag).into()),
+        }
+    }
+}
+
+impl HunspellDictionary {
+    pub fn parse(aff: &str, dic: &str) -> Result<HunspellDictionary, Box<dyn Error>> {
+        let mut dictionary = HunspellDictionary {
+            stems: HashMap::new(),
+            affixes: Vec::new(),
+            by_text: HashMap::new(),
+            by_flag: HashMap::new(),
+            longest_affix: 0,
+            need_affix: None,
+            forbidden: None,
+        };
+        let mut flags = FlagParser {
+            flag_type: FlagType::Char,
+            aliases: Vec::new(),
+        };
+        let mut seen_alias_count = false;
+        // Cross product permission of each affix class, from its header line.
+        let mut cross_products: HashMap<(AffixKind, Flag), bool> = HashMap::new();
+        for (line_number, line) in aff.lines().enumerate() {
+            let error = |e: Box<dyn Error>| format!("aff line {}: {}", line_number + 1, e);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long"] => flags.flag_type = FlagType::Long,
+                ["FLAG", "num"] => flags.flag_type = FlagType::Num,
+                ["FLAG", _] => flags.flag_type = FlagType::Char,
+                ["AF", _, ..] if !seen_alias_count => seen_alias_count = true,
+                ["AF", alias, ..] => flags.aliases.push(flags.parse_flags(alias).map_err(error)?),
+                ["NEEDAFFIX", flag, ..] => {
+                    dictionary.need_affix = Some(flags.parse_one(flag).map_err(error)?)
+                }
+                ["FORBIDDENWORD", flag, ..] => {
+                    dictionary.forbidden = Some(flags.parse_one(flag).map_err(error)?)
+                }
+                [kind @ ("PFX" | "SFX"), flag, rest @ ..] => {
+                    let kind = if *kind == "PFX" {
+                        AffixKind::Prefix
+                    } else {
+                        AffixKind::Suffix
+                    };
+                    let flag = flags.parse_one(flag).map_err(error)?;
+                    let cross_product = match cross_products.get(&(kind, flag)) {
+                        Some(cross_product) => *cross_product,
+                        None => {
+                            cross_products.insert((kind, flag), rest.first() == Some(&"Y"));
+                            continue;
+                        }
+                    };
+                    let (strip, affix, condition) = match rest {
+                        [strip, affix] => (strip, affix, "."),
+                        [strip, affix, condition, ..] => (strip, affix, *condition),
+                        _ => return Err(error("malformed affix rule".into()).into()),
+                    };
+                    let (affix, continuation) = match affix.split_once('/') {
+                        Some((affix, continuation)) => {
+                            (affix, flags.parse(continuation).map_err(error)?)
+                        }
+                        None => (*affix, Vec::new()),
+                    };
+                    dictionary.add_affix(Affix {
+                        kind,
+                        flag,
+                        cross_product,
+                        strip: parse_affix_text(strip),
+                        affix: parse_affix_text(affix),
+                        condition: parse_condition(condition).map_err(error)?,
+                        continuation,
+                    });
+                }
+                _ => (),
+            }
+        }
+        // The first line of a .dic file is the approximate number of stems.
+        for (line_number, line) in dic.lines().enumerate().skip(1) {
+            let entry = match line.split_whitespace().next() {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let (stem, stem_flags) = match entry.split_once('/') {
+                Some((stem, stem_flags)) => (
+                    stem,
+                    flags
+                        .parse(stem_flags)
+                        .map_err(|e| format!("dic line {}: {}", line_number + 1, e))?,
+                ),
+                None => (entry, Vec::new()),
+            };
+            dictionary
+                .stems
+                .entry(String::from(stem))
+                .or_default()
+                .push(stem_flags);
+        }
+        Ok(dictionary)
+    }
+
+    /// Loads a `.dic` file and the `.aff` file next to it.
+    pub fn load(dic_filename: &str) -> Result<HunspellDictionary, Box<dyn Error>> {
+        let aff_filename = match dic_filename.strip_suffix(".dic") {
+            Some(base) => format!("{}.aff", base),
+            None => return Err(format!("{} is not a .dic file", dic_filename).into()),
+        };
+        let aff = std::fs::read(&aff_filename)?;
+        let dic = std::fs::read(dic_filename)?;
+        HunspellDictionary::parse(&decode(&aff, &aff), &decode(&dic, &aff))
+    }
+
+    fn add_affix(&mut self, affix: Affix) {
+        let id = self.affixes.len();
+        self.longest_affix = self.longest_affix.max(affix.affix.chars().count());
+        self.by_text
+            .entry((affix.kind, affix.affix.clone()))
+            .or_default()
+            .push(id);
+        self.by_flag.entry(affix.flag).or_default().push(id);
+        self.affixes.push(affix);
+    }
+
+    fn has_flag(flags: &[Flag], flag: Option<Flag>) -> bool {
+        flag.map(|x| flags.contains(&x)).unwrap_or(false)
+    }
+
+    /// Whether some entry of `stem` carries all of `required` flags.
+    fn stem_has_flags(&self, stem: &str, required: &[Flag]) -> bool {
+        self.stems.get(stem).into_iter().flatten().any(|flags| {
+            !HunspellDictionary::has_flag(flags, self.forbidden)
+                && required.iter().all(|x| flags.contains(x))
+        })
+    }
+
+    /// Every way to take an affix of `kind` off `word`, with the stem that
+    /// is left.
+    fn strip_affixes(&self, word: &str, kind: AffixKind) -> Vec<(&Affix, String)> {
+        let chars: Vec<char> = word.chars().collect();
+        let mut stripped = Vec::new();
+        for length in 0..=self.longest_affix.min(chars.len()) {
+            let (affix, rest): (String, String) = match kind {
+                AffixKind::Prefix => (
+                    chars[..length].iter().collect(),
+                    chars[length..].iter().collect(),
+                ),
+                AffixKind::Suffix => (
+                    chars[chars.len() - length..].iter().collect(),
+                    chars[..chars.len() - length].iter().collect(),
+                ),
+            };
+            for id in self.by_text.get(&(kind, affix)).into_iter().flatten() {
+                let affix = &self.affixes[*id];
+                let stem = match kind {
+                    AffixKind::Prefix => format!("{}{}", affix.strip, rest),
+                    AffixKind::Suffix => format!("{}{}", rest, affix.strip),
+                };
+                if !stem.is_empty() && affix.applies_to(&stem) {
+                    stripped.push((affix, stem));
+                }
+            }
+        }
+        stripped
+    }
+
+    /// Whether the case-sensitive `word` is a stem or an affixed form of one.
+    pub fn check(&self, word: &str) -> bool {
+        if let Some(entries) = self.stems.get(word) {
+            if entries
+                .iter()
+                .any(|x| HunspellDictionary::has_flag(x, self.forbidden))
+            {
+                return false;
+            }
+            if entries
+                .iter()
+                .any(|x| !HunspellDictionary::has_flag(x, self.need_affix))
+            {
+                return true;
+            }
+        }
+        for (suffix, stem) in self.strip_affixes(word, AffixKind::Suffix) {
+            if self.stem_has_flags(&stem, &[suffix.flag]) {
+                return true;
+            }
+            // A suffix on top of another one, as the continuation classes of
+            // the inner suffix allow.
+            for (inner, base) in self.strip_affixes(&stem, AffixKind::Suffix) {
+                if inner.continuation.contains(&suffix.flag)
+                    && self.stem_has_flags(&base, &[inner.flag])
+                {
+                    return true;
+                }
+            }
+        }
+        for (prefix, rest) in self.strip_affixes(word, AffixKind::Prefix) {
+            if self.stem_has_flags(&rest, &[prefix.flag]) {
+                return true;
+            }
+            if !prefix.cross_product {
+                continue;
+            }
+            for (suffix, stem) in self.strip_affixes(&rest, AffixKind::Suffix) {
+                if suffix.cross_product && self.stem_has_flags(&stem, &[prefix.flag, suffix.flag]) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn affixes_with_flags<'a>(
+        &'a self,
+        flags: &'a [Flag],
+        kind: AffixKind,
+    ) -> impl Iterator<Item = &'a Affix> + 'a {
+        flags
+            .iter()
+            .filter_map(move |x| self.by_flag.get(x))
+            .flatten()
+            .map(move |x| &self.affixes[*x])
+            .filter(move |x| x.kind == kind)
+    }
+
+    /// Every form the affix rules make of a stem with `flags`.
+    fn forms(&self, stem: &str, flags: &[Flag]) -> Vec<String> {
+        if HunspellDictionary::has_flag(flags, self.forbidden) {
+            return Vec::new();
+        }
+        let mut forms = Vec::new();
+        if !HunspellDictionary::has_flag(flags, self.need_affix) {
+            forms.push(String::from(stem));
+        }
+        for suffix in self.affixes_with_flags(flags, AffixKind::Suffix) {
+            if let Some(form) = suffix.apply(stem) {
+                for outer in self.affixes_with_flags(&suffix.continuation, AffixKind::Suffix) {
+                    forms.extend(outer.apply(&form));
+                }
+                for prefix in self.affixes_with_flags(flags, AffixKind::Prefix) {
+                    if prefix.cross_product && suffix.cross_product {
+                        forms.extend(prefix.apply(&form));
+                    }
+                }
+                forms.push(form);
+            }
+        }
+        for prefix in self.affixes_with_flags(flags, AffixKind::Prefix) {
+            forms.extend(prefix.apply(stem));
+        }
+        forms
+    }
+}
+
+fn capitalized(word: &str) -> Option<String> {
+    let mut chars = word.chars();
+    let first = chars.next()?;
+    if first.is_uppercase() {
+        return None;
+    }
+    Some(first.to_uppercase().chain(chars).collect())
+}
+
+impl Dictionary for HunspellDictionary {
+    /// Lowercase words also match capitalized stems, since the corrector
+    /// looks words up lowercased and dictionaries list names capitalized.
+    fn contains(&self, word: &str) -> bool {
+        self.check(word) || capitalized(word).map(|x| self.check(&x)).unwrap_or(false)
+    }
+
+    /// Generates the forms of every stem, so this is a full scan.
+    fn words_with_prefix(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let mut words: Vec<String> = self
+            .stems
+            .iter()
+            .flat_map(|(stem, entries)| entries.iter().map(move |flags| (stem, flags)))
+            .flat_map(|(stem, flags)| self.forms(stem, flags))
+            .filter(|x| x.starts_with(prefix))
+            .collect();
+        words.sort();
+        words.dedup();
+        words.truncate(limit);
+        words
+    }
+
+    /// The number of stems, not of the forms the affix rules make of them.
+    fn len(&self) -> usize {
+        self.stems.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8
+FLAG long
+NEEDAFFIX Na
+FORBIDDENWORD Fb
+
+PFX Pe Y 1
+PFX Pe 0 пере .
+
+SFX Nm Y 3
+SFX Nm 0 а [^аь]
+SFX Nm 0 ом [^аь]
+SFX Nm ь я ь
+
+SFX Aj N 1
+SFX Aj 0 ый .
+
+SFX Vb Y 2
+SFX Vb ть л/Rf ать
+SFX Vb ть ли/Rf ать
+
+SFX Rf N 2
+SFX Rf 0 ся л
+SFX Rf 0 сь и
+";
+
+    const DIC: &str = "5
+привет/Nm
+конь/Nm
+бел/NaAj
+делать/VbPe
+приветом/Fb
+";
+
+    #[test]
+    fn checks_affixed_forms() {
+        let dictionary = HunspellDictionary::parse(AFF, DIC).unwrap();
+        for word in &[
+            "привет",
+            "привета",
+            "коня",
+            "делал",
+            "переделать",
+            "переделал",
+            "переделали",
+            "делался",
+            "делались",
+            "белый",
+        ] {
+            assert!(dictionary.contains(word), "{}", word);
+        }
+        for word in &[
+            "приветя",
+            "коньа",
+            "перепривет",
+            "приветом",
+            "бел",
+            "делалсь",
+        ] {
+            assert!(!dictionary.contains(word), "{}", word);
+        }
+        assert_eq!(
+            dictionary.words_with_prefix("переде", 10),
+            vec!["переделал", "переделали", "переделать"]
+        );
+    }
+}
@@ -0,0 +1,497 @@
+// Keysym names understood by the XKB importer, generated from X11/keysymdef.h.
+// Only the Latin, Cyrillic and punctuation ranges that keyboard layouts for our
+// languages use are kept; other symbols can still be given as `Uxxxx`.
+pub const KEYSYMS: &[(&str, char)] = &[
+    ("0", '0'),
+    ("1", '1'),
+    ("2", '2'),
+    ("3", '3'),
+    ("4", '4'),
+    ("5", '5'),
+    ("6", '6'),
+    ("7", '7'),
+    ("8", '8'),
+    ("9", '9'),
+    ("A", 'A'),
+    ("AE", 'Æ'),
+    ("Aacute", 'Á'),
+    ("Abreve", 'Ă'),
+    ("Acircumflex", 'Â'),
+    ("Adiaeresis", 'Ä'),
+    ("Agrave", 'À'),
+    ("Amacron", 'Ā'),
+    ("Aogonek", 'Ą'),
+    ("Aring", 'Å'),
+    ("Atilde", 'Ã'),
+    ("B", 'B'),
+    ("Byelorussian_SHORTU", 'Ў'),
+    ("Byelorussian_shortu", 'ў'),
+    ("C", 'C'),
+    ("Cabovedot", 'Ċ'),
+    ("Cacute", 'Ć'),
+    ("Ccaron", 'Č'),
+    ("Ccedilla", 'Ç'),
+    ("Ccircumflex", 'Ĉ'),
+    ("ColonSign", '₡'),
+    ("CruzeiroSign", '₢'),
+    ("Cyrillic_A", 'А'),
+    ("Cyrillic_BE", 'Б'),
+    ("Cyrillic_CHE", 'Ч'),
+    ("Cyrillic_CHE_descender", 'Ҷ'),
+    ("Cyrillic_CHE_vertstroke", 'Ҹ'),
+    ("Cyrillic_DE", 'Д'),
+    ("Cyrillic_DZHE", 'Џ'),
+    ("Cyrillic_E", 'Э'),
+    ("Cyrillic_EF", 'Ф'),
+    ("Cyrillic_EL", 'Л'),
+    ("Cyrillic_EM", 'М'),
+    ("Cyrillic_EN", 'Н'),
+    ("Cyrillic_EN_descender", 'Ң'),
+    ("Cyrillic_ER", 'Р'),
+    ("Cyrillic_ES", 'С'),
+    ("Cyrillic_GHE", 'Г'),
+    ("Cyrillic_GHE_bar", 'Ғ'),
+    ("Cyrillic_HA", 'Х'),
+    ("Cyrillic_HARDSIGN", 'Ъ'),
+    ("Cyrillic_HA_descender", 'Ҳ'),
+    ("Cyrillic_I", 'И'),
+    ("Cyrillic_IE", 'Е'),
+    ("Cyrillic_IO", 'Ё'),
+    ("Cyrillic_I_macron", 'Ӣ'),
+    ("Cyrillic_JE", 'Ј'),
+    ("Cyrillic_KA", 'К'),
+    ("Cyrillic_KA_descender", 'Қ'),
+    ("Cyrillic_KA_vertstroke", 'Ҝ'),
+    ("Cyrillic_LJE", 'Љ'),
+    ("Cyrillic_NJE", 'Њ'),
+    ("Cyrillic_O", 'О'),
+    ("Cyrillic_O_bar", 'Ө'),
+    ("Cyrillic_PE", 'П'),
+    ("Cyrillic_SCHWA", 'Ә'),
+    ("Cyrillic_SHA", 'Ш'),
+    ("Cyrillic_SHCHA", 'Щ'),
+    ("Cyrillic_SHHA", 'Һ'),
+    ("Cyrillic_SHORTI", 'Й'),
+    ("Cyrillic_SOFTSIGN", 'Ь'),
+    ("Cyrillic_TE", 'Т'),
+    ("Cyrillic_TSE", 'Ц'),
+    ("Cyrillic_U", 'У'),
+    ("Cyrillic_U_macron", 'Ӯ'),
+    ("Cyrillic_U_straight", 'Ү'),
+    ("Cyrillic_U_straight_bar", 'Ұ'),
+    ("Cyrillic_VE", 'В'),
+    ("Cyrillic_YA", 'Я'),
+    ("Cyrillic_YERU", 'Ы'),
+    ("Cyrillic_YU", 'Ю'),
+    ("Cyrillic_ZE", 'З'),
+    ("Cyrillic_ZHE", 'Ж'),
+    ("Cyrillic_ZHE_descender", 'Җ'),
+    ("Cyrillic_a", 'а'),
+    ("Cyrillic_be", 'б'),
+    ("Cyrillic_che", 'ч'),
+    ("Cyrillic_che_descender", 'ҷ'),
+    ("Cyrillic_che_vertstroke", 'ҹ'),
+    ("Cyrillic_de", 'д'),
+    ("Cyrillic_dzhe", 'џ'),
+    ("Cyrillic_e", 'э'),
+    ("Cyrillic_ef", 'ф'),
+    ("Cyrillic_el", 'л'),
+    ("Cyrillic_em", 'м'),
+    ("Cyrillic_en", 'н'),
+    ("Cyrillic_en_descender", 'ң'),
+    ("Cyrillic_er", 'р'),
+    ("Cyrillic_es", 'с'),
+    ("Cyrillic_ghe", 'г'),
+    ("Cyrillic_ghe_bar", 'ғ'),
+    ("Cyrillic_ha", 'х'),
+    ("Cyrillic_ha_descender", 'ҳ'),
+    ("Cyrillic_hardsign", 'ъ'),
+    ("Cyrillic_i", 'и'),
+    ("Cyrillic_i_macron", 'ӣ'),
+    ("Cyrillic_ie", 'е'),
+    ("Cyrillic_io", 'ё'),
+    ("Cyrillic_je", 'ј'),
+    ("Cyrillic_ka", 'к'),
+    ("Cyrillic_ka_descender", 'қ'),
+    ("Cyrillic_ka_vertstroke", 'ҝ'),
+    ("Cyrillic_lje", 'љ'),
+    ("Cyrillic_nje", 'њ'),
+    ("Cyrillic_o", 'о'),
+    ("Cyrillic_o_bar", 'ө'),
+    ("Cyrillic_pe", 'п'),
+    ("Cyrillic_schwa", 'ә'),
+    ("Cyrillic_sha", 'ш'),
+    ("Cyrillic_shcha", 'щ'),
+    ("Cyrillic_shha", 'һ'),
+    ("Cyrillic_shorti", 'й'),
+    ("Cyrillic_softsign", 'ь'),
+    ("Cyrillic_te", 'т'),
+    ("Cyrillic_tse", 'ц'),
+    ("Cyrillic_u", 'у'),
+    ("Cyrillic_u_macron", 'ӯ'),
+    ("Cyrillic_u_straight", 'ү'),
+    ("Cyrillic_u_straight_bar", 'ұ'),
+    ("Cyrillic_ve", 'в'),
+    ("Cyrillic_ya", 'я'),
+    ("Cyrillic_yeru", 'ы'),
+    ("Cyrillic_yu", 'ю'),
+    ("Cyrillic_ze", 'з'),
+    ("Cyrillic_zhe", 'ж'),
+    ("Cyrillic_zhe_descender", 'җ'),
+    ("D", 'D'),
+    ("Dcaron", 'Ď'),
+    ("Delete", ''),
+    ("DongSign", '₫'),
+    ("Dstroke", 'Đ'),
+    ("E", 'E'),
+    ("ENG", 'Ŋ'),
+    ("ETH", 'Ð'),
+    ("Eabovedot", 'Ė'),
+    ("Eacute", 'É'),
+    ("Ecaron", 'Ě'),
+    ("Ecircumflex", 'Ê'),
+    ("EcuSign", '₠'),
+    ("Ediaeresis", 'Ë'),
+    ("Egrave", 'È'),
+    ("Emacron", 'Ē'),
+    ("Eogonek", 'Ę'),
+    ("EuroSign", '€'),
+    ("F", 'F'),
+    ("FFrancSign", '₣'),
+    ("G", 'G'),
+    ("Gabovedot", 'Ġ'),
+    ("Gbreve", 'Ğ'),
+    ("Gcedilla", 'Ģ'),
+    ("Gcircumflex", 'Ĝ'),
+    ("Greek_horizbar", '―'),
+    ("H", 'H'),
+    ("Hcircumflex", 'Ĥ'),
+    ("Hstroke", 'Ħ'),
+    ("I", 'I'),
+    ("Iabovedot", 'İ'),
+    ("Iacute", 'Í'),
+    ("Ibreve", 'Ĭ'),
+    ("Icircumflex", 'Î'),
+    ("Idiaeresis", 'Ï'),
+    ("Igrave", 'Ì'),
+    ("Imacron", 'Ī'),
+    ("Iogonek", 'Į'),
+    ("Itilde", 'Ĩ'),
+    ("J", 'J'),
+    ("Jcircumflex", 'Ĵ'),
+    ("K", 'K'),
+    ("Kcedilla", 'Ķ'),
+    ("Korean_Won", '₩'),
+    ("L", 'L'),
+    ("Lacute", 'Ĺ'),
+    ("Lcaron", 'Ľ'),
+    ("Lcedilla", 'Ļ'),
+    ("LiraSign", '₤'),
+    ("Lstroke", 'Ł'),
+    ("M", 'M'),
+    ("Macedonia_DSE", 'Ѕ'),
+    ("Macedonia_GJE", 'Ѓ'),
+    ("Macedonia_KJE", 'Ќ'),
+    ("Macedonia_dse", 'ѕ'),
+    ("Macedonia_gje", 'ѓ'),
+    ("Macedonia_kje", 'ќ'),
+    ("MillSign", '₥'),
+    ("N", 'N'),
+    ("Nacute", 'Ń'),
+    ("NairaSign", '₦'),
+    ("Ncaron", 'Ň'),
+    ("Ncedilla", 'Ņ'),
+    ("NewSheqelSign", '₪'),
+    ("Ntilde", 'Ñ'),
+    ("O", 'O'),
+    ("OE", 'Œ'),
+    ("Oacute", 'Ó'),
+    ("Ocircumflex", 'Ô'),
+    ("Odiaeresis", 'Ö'),
+    ("Odoubleacute", 'Ő'),
+    ("Ograve", 'Ò'),
+    ("Omacron", 'Ō'),
+    ("Oslash", 'Ø'),
+    ("Otilde", 'Õ'),
+    ("P", 'P'),
+    ("PesetaSign", '₧'),
+    ("Q", 'Q'),
+    ("R", 'R'),
+    ("Racute", 'Ŕ'),
+    ("Rcaron", 'Ř'),
+    ("Rcedilla", 'Ŗ'),
+    ("RupeeSign", '₨'),
+    ("S", 'S'),
+    ("Sacute", 'Ś'),
+    ("Scaron", 'Š'),
+    ("Scedilla", 'Ş'),
+    ("Scircumflex", 'Ŝ'),
+    ("Serbian_DJE", 'Ђ'),
+    ("Serbian_TSHE", 'Ћ'),
+    ("Serbian_dje", 'ђ'),
+    ("Serbian_tshe", 'ћ'),
+    ("T", 'T'),
+    ("THORN", 'Þ'),
+    ("Tcaron", 'Ť'),
+    ("Tcedilla", 'Ţ'),
+    ("Tslash", 'Ŧ'),
+    ("U", 'U'),
+    ("Uacute", 'Ú'),
+    ("Ubreve", 'Ŭ'),
+    ("Ucircumflex", 'Û'),
+    ("Udiaeresis", 'Ü'),
+    ("Udoubleacute", 'Ű'),
+    ("Ugrave", 'Ù'),
+    ("Ukrainian_GHE_WITH_UPTURN", 'Ґ'),
+    ("Ukrainian_I", 'І'),
+    ("Ukrainian_IE", 'Є'),
+    ("Ukrainian_YI", 'Ї'),
+    ("Ukrainian_ghe_with_upturn", 'ґ'),
+    ("Ukrainian_i", 'і'),
+    ("Ukrainian_ie", 'є'),
+    ("Ukrainian_yi", 'ї'),
+    ("Umacron", 'Ū'),
+    ("Uogonek", 'Ų'),
+    ("Uring", 'Ů'),
+    ("Utilde", 'Ũ'),
+    ("V", 'V'),
+    ("W", 'W'),
+    ("Wcircumflex", 'Ŵ'),
+    ("WonSign", '₩'),
+    ("X", 'X'),
+    ("Y", 'Y'),
+    ("Yacute", 'Ý'),
+    ("Ycircumflex", 'Ŷ'),
+    ("Ydiaeresis", 'Ÿ'),
+    ("Z", 'Z'),
+    ("Zabovedot", 'Ż'),
+    ("Zacute", 'Ź'),
+    ("Zcaron", 'Ž'),
+    ("a", 'a'),
+    ("aacute", 'á'),
+    ("abreve", 'ă'),
+    ("acircumflex", 'â'),
+    ("acute", '´'),
+    ("adiaeresis", 'ä'),
+    ("ae", 'æ'),
+    ("agrave", 'à'),
+    ("amacron", 'ā'),
+    ("ampersand", '&'),
+    ("aogonek", 'ą'),
+    ("apostrophe", '\''),
+    ("aring", 'å'),
+    ("asciicircum", '^'),
+    ("asciitilde", '~'),
+    ("asterisk", '*'),
+    ("at", '@'),
+    ("atilde", 'ã'),
+    ("b", 'b'),
+    ("backslash", '\\'),
+    ("bar", '|'),
+    ("braceleft", '{'),
+    ("braceright", '}'),
+    ("bracketleft", '['),
+    ("bracketright", ']'),
+    ("brokenbar", '¦'),
+    ("c", 'c'),
+    ("cabovedot", 'ċ'),
+    ("cacute", 'ć'),
+    ("caret", '‸'),
+    ("ccaron", 'č'),
+    ("ccedilla", 'ç'),
+    ("ccircumflex", 'ĉ'),
+    ("cedilla", '¸'),
+    ("cent", '¢'),
+    ("colon", ':'),
+    ("comma", ','),
+    ("copyright", '©'),
+    ("currency", '¤'),
+    ("d", 'd'),
+    ("dagger", '†'),
+    ("dcaron", 'ď'),
+    ("decimalpoint", '.'),
+    ("degree", '°'),
+    ("diaeresis", '¨'),
+    ("division", '÷'),
+    ("dollar", '$'),
+    ("doubbaselinedot", '‥'),
+    ("doubledagger", '‡'),
+    ("doublelowquotemark", '„'),
+    ("dstroke", 'đ'),
+    ("e", 'e'),
+    ("eabovedot", 'ė'),
+    ("eacute", 'é'),
+    ("ecaron", 'ě'),
+    ("ecircumflex", 'ê'),
+    ("ediaeresis", 'ë'),
+    ("egrave", 'è'),
+    ("eightsubscript", '₈'),
+    ("eightsuperior", '⁸'),
+    ("ellipsis", '…'),
+    ("emacron", 'ē'),
+    ("emdash", '—'),
+    ("endash", '–'),
+    ("enfilledcircbullet", '•'),
+    ("eng", 'ŋ'),
+    ("eogonek", 'ę'),
+    ("equal", '='),
+    ("eth", 'ð'),
+    ("exclam", '!'),
+    ("exclamdown", '¡'),
+    ("f", 'f'),
+    ("figdash", '‒'),
+    ("fivesubscript", '₅'),
+    ("fivesuperior", '⁵'),
+    ("foursubscript", '₄'),
+    ("foursuperior", '⁴'),
+    ("g", 'g'),
+    ("gabovedot", 'ġ'),
+    ("gbreve", 'ğ'),
+    ("gcedilla", 'ģ'),
+    ("gcircumflex", 'ĝ'),
+    ("grave", '`'),
+    ("greater", '>'),
+    ("guillemetleft", '«'),
+    ("guillemetright", '»'),
+    ("h", 'h'),
+    ("hcircumflex", 'ĥ'),
+    ("hebrew_doublelowline", '‗'),
+    ("hstroke", 'ħ'),
+    ("hyphen", '\u{ad}'),
+    ("i", 'i'),
+    ("iacute", 'í'),
+    ("ibreve", 'ĭ'),
+    ("icircumflex", 'î'),
+    ("idiaeresis", 'ï'),
+    ("idotless", 'ı'),
+    ("igrave", 'ì'),
+    ("imacron", 'ī'),
+    ("iogonek", 'į'),
+    ("itilde", 'ĩ'),
+    ("j", 'j'),
+    ("jcircumflex", 'ĵ'),
+    ("k", 'k'),
+    ("kcedilla", 'ķ'),
+    ("kra", 'ĸ'),
+    ("l", 'l'),
+    ("lacute", 'ĺ'),
+    ("lcaron", 'ľ'),
+    ("lcedilla", 'ļ'),
+    ("leftcaret", '<'),
+    ("leftdoublequotemark", '“'),
+    ("leftsinglequotemark", '‘'),
+    ("less", '<'),
+    ("lstroke", 'ł'),
+    ("m", 'm'),
+    ("macron", '¯'),
+    ("minus", '-'),
+    ("minutes", '′'),
+    ("mu", 'µ'),
+    ("multiply", '×'),
+    ("n", 'n'),
+    ("nacute", 'ń'),
+    ("ncaron", 'ň'),
+    ("ncedilla", 'ņ'),
+    ("ninesubscript", '₉'),
+    ("ninesuperior", '⁹'),
+    ("nobreakspace", '\u{a0}'),
+    ("notsign", '¬'),
+    ("ntilde", 'ñ'),
+    ("numbersign", '#'),
+    ("numerosign", '№'),
+    ("o", 'o'),
+    ("oacute", 'ó'),
+    ("ocircumflex", 'ô'),
+    ("odiaeresis", 'ö'),
+    ("odoubleacute", 'ő'),
+    ("oe", 'œ'),
+    ("ograve", 'ò'),
+    ("omacron", 'ō'),
+    ("onehalf", '½'),
+    ("onequarter", '¼'),
+    ("onesubscript", '₁'),
+    ("onesuperior", '¹'),
+    ("ordfeminine", 'ª'),
+    ("ordmasculine", 'º'),
+    ("oslash", 'ø'),
+    ("otilde", 'õ'),
+    ("overbar", '¯'),
+    ("overline", '‾'),
+    ("p", 'p'),
+    ("paragraph", '¶'),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("percent", '%'),
+    ("period", '.'),
+    ("periodcentered", '·'),
+    ("permille", '‰'),
+    ("plus", '+'),
+    ("plusminus", '±'),
+    ("q", 'q'),
+    ("question", '?'),
+    ("questiondown", '¿'),
+    ("quotedbl", '"'),
+    ("r", 'r'),
+    ("racute", 'ŕ'),
+    ("rcaron", 'ř'),
+    ("rcedilla", 'ŗ'),
+    ("registered", '®'),
+    ("rightcaret", '>'),
+    ("rightdoublequotemark", '”'),
+    ("rightsinglequotemark", '’'),
+    ("s", 's'),
+    ("sacute", 'ś'),
+    ("scaron", 'š'),
+    ("scedilla", 'ş'),
+    ("scircumflex", 'ŝ'),
+    ("seconds", '″'),
+    ("section", '§'),
+    ("semicolon", ';'),
+    ("sevensubscript", '₇'),
+    ("sevensuperior", '⁷'),
+    ("singlelowquotemark", '‚'),
+    ("sixsubscript", '₆'),
+    ("sixsuperior", '⁶'),
+    ("slash", '/'),
+    ("space", ' '),
+    ("ssharp", 'ß'),
+    ("sterling", '£'),
+    ("t", 't'),
+    ("tcaron", 'ť'),
+    ("tcedilla", 'ţ'),
+    ("thorn", 'þ'),
+    ("threequarters", '¾'),
+    ("threesubscript", '₃'),
+    ("threesuperior", '³'),
+    ("tslash", 'ŧ'),
+    ("twosubscript", '₂'),
+    ("twosuperior", '²'),
+    ("u", 'u'),
+    ("uacute", 'ú'),
+    ("ubreve", 'ŭ'),
+    ("ucircumflex", 'û'),
+    ("udiaeresis", 'ü'),
+    ("udoubleacute", 'ű'),
+    ("ugrave", 'ù'),
+    ("umacron", 'ū'),
+    ("underbar", '_'),
+    ("underscore", '_'),
+    ("uogonek", 'ų'),
+    ("uring", 'ů'),
+    ("utilde", 'ũ'),
+    ("v", 'v'),
+    ("w", 'w'),
+    ("wcircumflex", 'ŵ'),
+    ("x", 'x'),
+    ("y", 'y'),
+    ("yacute", 'ý'),
+    ("ycircumflex", 'ŷ'),
+    ("ydiaeresis", 'ÿ'),
+    ("yen", '¥'),
+    ("z", 'z'),
+    ("zabovedot", 'ż'),
+    ("zacute", 'ź'),
+    ("zcaron", 'ž'),
+    ("zerosubscript", '₀'),
+    ("zerosuperior", '⁰'),
+];
@@ -0,0 +1,77 @@
+use crate::dictionary::Dictionary;
+use crate::fuzzy::FuzzyIndex;
+use crate::layout::Layout;
+use std::collections::HashSet;
+
+// Language codes the bot knows about and the layout each one is typed on
+// unless configured otherwise.
+const DEFAULT_LAYOUTS: &[(&str, &str)] = &[
+    ("en", "us"),
+    ("ru", "ru"),
+    ("uk", "ua"),
+    ("be", "by"),
+    ("kk", "kz"),
+];
+
+/// Apostrophes and hyphens, which are part of a word when inside it, as in
+/// "м'ясо" or "по-русски".
+pub const INTRA_WORD: &[char] = &['\'', '\u{2019}', '\u{2bc}', '-'];
+
+/// A language text can be corrected into: the layout its speakers type on
+/// and the words that recognise it.
+pub struct Language {
+    pub code: String,
+    pub layout: Layout,
+    pub words: Box<dyn Dictionary>,
+    letters: HashSet<char>,
+    typos: Option<FuzzyIndex>,
+}
+
+impl Language {
+    pub fn new(code: &str, layout: Layout, words: Box<dyn Dictionary>) -> Language {
+        Language {
+            code: String::from(code),
+            letters: layout.letters(),
+            layout,
+            words,
+            typos: None,
+        }
+    }
+
+    /// Indexes `words`, e.g. the most frequent ones of the dictionary, so
+    /// that `nearest_word` can find them up to `max_distance` edits away. The
+    /// index holds a copy of every word and many deletes of it.
+    pub fn index_typos(&mut self, words: Vec<String>, max_distance: usize) {
+        self.typos = Some(FuzzyIndex::new(words, max_distance));
+    }
+
+    /// The known word `word` is most likely a misspelling of, if typos are
+    /// indexed.
+    pub fn nearest_word(&self, word: &str) -> Option<&str> {
+        self.typos.as_ref().and_then(|x| x.nearest(word))
+    }
+
+    /// Whether every letter of `text` can be typed on this language's layout.
+    pub fn can_type(&self, text: &str) -> bool {
+        let mut letters = text.chars().filter(|x| x.is_alphabetic()).peekable();
+        letters.peek().is_some() && letters.all(|x| self.letters.contains(&x))
+    }
+
+    /// Whether `text` contains any letter of this language's layout.
+    pub fn uses_letters_of(&self, text: &str) -> bool {
+        text.chars().any(|x| self.letters.contains(&x))
+    }
+
+    /// Whether the two languages are typed on layouts without common letters,
+    /// so that a message typed on one can be mistaken for the other.
+    pub fn is_distinguishable_from(&self, other: &Language) -> bool {
+        self.letters.is_disjoint(&other.letters)
+    }
+}
+
+pub fn default_layout(code: &str) -> Option<Layout> {
+    DEFAULT_LAYOUTS
+        .iter()
+        .find(|(language, _)| *language == code)
+        .and_then(|(_, layout)| Layout::builtin(layout))
+}
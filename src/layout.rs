@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::iter::FromIterator;
+use std::path::Path;
+
+const BUILTIN_LAYOUTS: &[(&str, &str)] = &[
+    ("us", include_str!("../layouts/us.layout")),
+    ("ru", include_str!("../layouts/ru.layout")),
+    ("ua", include_str!("../layouts/ua.layout")),
+    ("by", include_str!("../layouts/by.layout")),
+    ("kz", include_str!("../layouts/kz.layout")),
+];
+
+/// Characters produced by each key position, one entry per Shift level.
+///
+/// Key positions use the XKB names (`AD01` is the key right of Tab, `AC10` is
+/// `;` on a US keyboard and so on), so that layouts coming from different
+/// sources line up with each other.
+pub struct Layout {
+    name: String,
+    keys: BTreeMap<String, Vec<char>>,
+}
+
+impl Layout {
+    /// Parses a layout definition: one key per line, the key position followed
+    /// by a single character for every Shift level. Empty lines and lines
+    /// starting with `#` are ignored.
+    pub fn parse(name: &str, src: &str) -> Result<Layout, Box<dyn Error>> {
+        let mut keys = BTreeMap::new();
+        for (line_number, line) in src.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let position = fields.next().unwrap();
+            let mut levels = Vec::new();
+            for level in fields {
+                let mut chars = level.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => levels.push(c),
+                    _ => {
+                        return Err(format!(
+                            "{}:{}: '{}' is not a single character",
+                            name,
+                            line_number + 1,
+                            level
+                        )
+                        .into())
+                    }
+                }
+            }
+            if keys.insert(String::from(position), levels).is_some() {
+                return Err(format!(
+                    "{}:{}: key {} is defined twice",
+                    name,
+                    line_number + 1,
+                    position
+                )
+                .into());
+            }
+        }
+        Ok(Layout::from_keys(name, keys))
+    }
+
+    pub fn load(filename: &str) -> Result<Layout, Box<dyn Error>> {
+        let mut file = File::open(filename)?;
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+        let name = Path::new(filename)
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or(filename);
+        Layout::parse(name, &buf)
+    }
+
+    pub fn from_keys(name: &str, keys: BTreeMap<String, Vec<char>>) -> Layout {
+        Layout {
+            name: String::from(name),
+            keys,
+        }
+    }
+
+    /// One of the layouts shipped with the bot, named as in XKB: `us`, `ru`,
+    /// `ua`, `by` or `kz`.
+    pub fn builtin(name: &str) -> Option<Layout> {
+        BUILTIN_LAYOUTS
+            .iter()
+            .find(|(builtin_name, _)| *builtin_name == name)
+            .map(|(_, src)| Layout::parse(name, src).unwrap())
+    }
+
+    /// All letters the layout can type, in both cases.
+    pub fn letters(&self) -> HashSet<char> {
+        self.keys
+            .values()
+            .flatten()
+            .filter(|x| x.is_alphabetic())
+            .copied()
+            .collect()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Converts text typed with one layout active into what the same keystrokes
+/// would have produced with another one.
+pub struct LayoutPair {
+    chars_map: HashMap<char, char>,
+}
+
+impl LayoutPair {
+    pub fn new(from: &Layout, to: &Layout) -> LayoutPair {
+        let mut chars_map = HashMap::new();
+        for (position, from_levels) in &from.keys {
+            if let Some(to_levels) = to.keys.get(position) {
+                for (from_char, to_char) in from_levels.iter().zip(to_levels) {
+                    chars_map.entry(*from_char).or_insert(*to_char);
+                }
+            }
+        }
+        LayoutPair { chars_map }
+    }
+
+    pub fn convert(&self, src: &str) -> String {
+        String::from_iter(
+            src.chars()
+                .map(|x| self.chars_map.get(&x).copied().unwrap_or(x)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_chars(layout: &Layout) -> String {
+        layout.keys.values().flatten().collect()
+    }
+
+    #[test]
+    fn layout_mapping_is_bijection() {
+        let (us, ru) = (
+            Layout::builtin("us").unwrap(),
+            Layout::builtin("ru").unwrap(),
+        );
+        let (latin, cyrillic) = (all_chars(&us), all_chars(&ru));
+        assert_eq!(
+            latin.chars().collect::<HashSet<_>>().len(),
+            latin.chars().count()
+        );
+        assert_eq!(
+            cyrillic.chars().collect::<HashSet<_>>().len(),
+            cyrillic.chars().count()
+        );
+        assert_eq!(latin.chars().count(), cyrillic.chars().count());
+        assert_eq!(LayoutPair::new(&us, &ru).convert(&latin), cyrillic);
+        assert_eq!(LayoutPair::new(&ru, &us).convert(&cyrillic), latin);
+    }
+
+    #[test]
+    fn shift_digit_row() {
+        let to_russian = LayoutPair::new(
+            &Layout::builtin("us").unwrap(),
+            &Layout::builtin("ru").unwrap(),
+        );
+        assert_eq!(to_russian.convert("@#$^&"), "\"№;:?");
+        assert_eq!(to_russian.convert("Ghbdtn& Rfr ltkf?"), "Привет? Как дела,");
+    }
+
+    #[test]
+    fn builtin_layouts_parse() {
+        for (name, _) in BUILTIN_LAYOUTS {
+            assert_eq!(Layout::builtin(name).unwrap().keys.len(), 47, "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_multi_character_levels() {
+        assert!(Layout::parse("bad", "AD01 q QQ").is_err());
+        assert!(Layout::parse("bad", "AD01 q Q\nAD01 w W").is_err());
+    }
+}
@@ -0,0 +1,18 @@
+pub mod corrector;
+pub mod detector;
+pub mod dictionary;
+pub mod entities;
+pub mod files;
+pub mod fuzzy;
+pub mod hunspell;
+mod keysyms;
+pub mod language;
+pub mod layout;
+pub mod morphology;
+pub mod ngram;
+pub mod outbox;
+pub mod settings;
+pub mod state;
+pub mod telegram;
+pub mod webhook;
+pub mod xkb;
@@ -1,180 +1,418 @@
-extern crate reqwest;
-
-use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::error::Error;
 use std::fs::File;
 use std::io::Read;
-use std::iter::FromIterator;
+use std::time::{Duration, Instant};
+
+use layout_corrector::corrector::{detect, Corrector, Detection};
+use layout_corrector::detector::{CombinedDetector, Detector, DictionaryDetector};
+use layout_corrector::dictionary::{load_dictionary, ranked_words};
+use layout_corrector::entities::{self, MessageEntity};
+use layout_corrector::language::{self, Language};
+use layout_corrector::layout::Layout;
+use layout_corrector::ngram::{NgramDetector, NgramModel};
+use layout_corrector::outbox::Outbox;
+use layout_corrector::settings::{ChatSettings, Settings, SettingsError, SETTING_NAMES};
+use layout_corrector::state::UpdateState;
+use layout_corrector::telegram::{
+    self, Chat, Client, DeleteMessage, EditMessageText, GetChatMember, GetUpdates, HttpTransport,
+    Message, SendMessage, SetWebhook, Update, User,
+};
+use layout_corrector::webhook::WebhookServer;
+use layout_corrector::xkb::{self, SymbolsDir};
+
+// Replied to a message, explains why it was or wasn't corrected; followed by
+// text, explains the decision about that text.
+const WHY_COMMAND: &str = "/why";
+
+// How many times a reply is tried when its errors are retryable.
+const SEND_ATTEMPTS: u32 = 3;
+
+// How long getUpdates waits for an update before returning none, and how long
+// a request may take in all, which must be longer.
+const POLL_TIMEOUT: Duration = Duration::from_secs(40);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(50);
+
+// At most this many updates are handled at once.
+const POLL_LIMIT: u32 = 100;
+
+// At most this many updates are kept from being confirmed while their replies
+// are queued, leaving getUpdates room for new ones.
+const MAX_HELD_UPDATES: usize = POLL_LIMIT as usize / 2;
+
+// The kinds of updates the bot handles.
+const ALLOWED_UPDATES: &[&str] = &["message", "edited_message"];
 
-fn fix_layout(src: &str) -> String {
-    let latin_chars = "qwertyuiop[]asdfghjkl;'zxcvbnm,./?`&";
-    let cyrillic_chars = "йцукенгшщзхъфывапролджэячсмитьбю.,ё?";
-    assert!(latin_chars.chars().count() == cyrillic_chars.chars().count());
-    let chars_map: HashMap<char, char> =
-        HashMap::from_iter(latin_chars.chars().zip(cyrillic_chars.chars()));
-    String::from_iter(
-        src.chars()
-            .map(|x| chars_map.get(&x).map(|y| *y).unwrap_or(x)),
-    )
+// How long to wait after a failed poll, doubling with each failure in a row.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+// At most this many of the words of a --typo-words list are indexed.
+const MAX_TYPO_WORDS: usize = 100_000;
+
+// Shows the chat's settings; chat admins can change or reset them with it.
+const SETTINGS_COMMAND: &str = "/settings";
+
+// While queued replies hold back the offset, getUpdates returns their updates
+// at once; polls bringing nothing new are spaced by this much.
+const REPEAT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+fn log_update(u: &Update) {
+    let message = u.message.as_ref().or(u.edited_message.as_ref());
+    println!(
+        "UPDATES_LOG: update_id={} chat_id={}: {}text={}",
+        u.update_id,
+        message.map(|x| x.chat.id).unwrap_or(-1),
+        if u.edited_message.is_some() {
+            "edited "
+        } else {
+            ""
+        },
+        message.and_then(|x| x.text_or_caption()).unwrap_or("NONE")
+    );
 }
 
-enum WordLanguage {
-    Russian,
-    NotRussian,
+/// What to do about the bot's reply to a message.
+enum Action {
+    Send(String),
+    Edit { reply_id: i64, text: String },
+    Delete { reply_id: i64 },
 }
 
-fn get_russians_ratio(src: &str, words: &HashSet<String>) -> f32 {
-    if src
-        .find(|x| "йцукенгшщзхъфывапролджэячсмитьбю".contains(x))
-        .is_some()
-    {
-        return -1.0;
-    }
-    let punctuation = "!\"#$%&\'()*+,-./:;<=>?@[\\]^_`{|}~";
-    let (total_count, russian_count) = src
-        .split_whitespace()
-        .map(|s| {
-            if words.contains(&String::from_iter(
-                fix_layout(s).chars().filter(|c| !punctuation.contains(*c)),
-            )) {
-                WordLanguage::Russian
-            } else {
-                WordLanguage::NotRussian
-            }
-        })
-        .fold((0, 0), |acc, elem| {
-            (
-                acc.0 + 1,
-                acc.1
-                    + match elem {
-                        WordLanguage::Russian => 1,
-                        WordLanguage::NotRussian => 0,
-                    },
-            )
-        });
-    if total_count != 0 {
-        println!("ratio is {}", russian_count as f32 / total_count as f32);
-        russian_count as f32 / total_count as f32
-    } else {
-        println!("ratio is 0.0");
-        0.0
-    }
-}
-
-#[derive(Serialize, Deserialize)]
-struct Chat {
-    first_name: Option<String>,
-    id: i64,
-    last_name: Option<String>,
-    username: Option<String>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Message {
-    chat: Chat,
-    date: i64,
-    text: Option<String>,
-    message_id: i64,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Update {
-    message: Option<Message>,
+/// A queued action on the reply to the message of an update.
+struct Reply {
     update_id: i64,
+    action: Action,
+}
+
+fn get_and_process_updates(
+    client: &Client,
+    bot_name: &str,
+    corrector: &Corrector,
+    chat_settings: &mut ChatSettings,
+    outbox: &mut Outbox<Reply>,
+    state: &mut UpdateState,
+) -> Result<bool, Box<dyn Error>> {
+    let timeout = receive_timeout(outbox);
+    let updates = client.get_updates(&GetUpdates {
+        offset: Some(state.offset(MAX_HELD_UPDATES)),
+        limit: Some(POLL_LIMIT),
+        timeout: Some(timeout.as_secs() + u64::from(timeout.subsec_nanos() > 0)),
+        allowed_updates: Some(allowed_updates()),
+    })?;
+    let received = updates.len();
+    let new = process_updates(
+        updates,
+        client,
+        bot_name,
+        corrector,
+        chat_settings,
+        outbox,
+        state,
+    );
+    Ok(received > 0 && new == 0)
 }
 
-fn log_updates(updates: &Vec<Update>) {
+/// Responds to updates however they were received, queueing the replies and
+/// skipping the updates received before. An edited message gets its reply
+/// edited, or deleted if it no longer needs one. Returns how many updates
+/// were new.
+fn process_updates(
+    updates: Vec<Update>,
+    client: &Client,
+    bot_name: &str,
+    corrector: &Corrector,
+    chat_settings: &mut ChatSettings,
+    outbox: &mut Outbox<Reply>,
+    state: &mut UpdateState,
+) -> usize {
+    let mut new = 0;
     for u in updates {
+        if state.is_done(u.update_id) || state.is_pending(u.update_id) {
+            continue;
+        }
+        log_update(&u);
+        new += 1;
+        state.start(u.update_id);
+        let message = match u.message.or(u.edited_message) {
+            Some(message) => message,
+            None => {
+                state.finish(u.update_id);
+                continue;
+            }
+        };
+        let chat_id = message.chat.id;
+        let message_id = message.message_id;
+        let text = respond(message, corrector, chat_settings, client, bot_name);
+        let sent = state
+            .reply(chat_id, message_id)
+            .map(|(reply_id, sent_text)| (reply_id, text.as_deref() == Some(sent_text)));
+        let action = match (text, sent) {
+            (Some(text), None) => Action::Send(text),
+            (Some(text), Some((reply_id, false))) => Action::Edit { reply_id, text },
+            (None, Some((reply_id, _))) => Action::Delete { reply_id },
+            // The reply is as it should be, or there is none and there should
+            // be none; an edit may have made a queued action unnecessary.
+            (Some(_), Some((_, true))) | (None, None) => {
+                if let Some(dropped) = outbox.remove(chat_id, message_id) {
+                    state.finish(dropped.update_id);
+                }
+                state.finish(u.update_id);
+                continue;
+            }
+        };
+        let reply = Reply {
+            update_id: u.update_id,
+            action,
+        };
+        if let Some(replaced) = outbox.push(chat_id, message_id, reply, Instant::now()) {
+            state.finish(replaced.update_id);
+        }
+    }
+    new
+}
+
+/// Saves which updates are done, logging failures: the worst outcome is that
+/// some get answered again after a restart.
+fn save_state(state: &mut UpdateState) {
+    if let Err(e) = state.save() {
+        println!("Saving the update state failed: {}", e);
+    }
+}
+
+fn allowed_updates() -> Vec<String> {
+    ALLOWED_UPDATES.iter().map(|x| String::from(*x)).collect()
+}
+
+/// How long to wait for updates before sending the queued replies that are
+/// due by then.
+fn receive_timeout(outbox: &mut Outbox<Reply>) -> Duration {
+    outbox
+        .next_ready_in(Instant::now())
+        .map_or(POLL_TIMEOUT, |x| x.min(POLL_TIMEOUT))
+}
+
+/// Sends the queued replies the rate limits allow, putting back those whose
+/// errors may go away and logging the rest: a reply that fails must never
+/// stop the bot.
+fn send_replies(client: &Client, outbox: &mut Outbox<Reply>, state: &mut UpdateState) {
+    for stale in outbox.drop_stale(Instant::now()) {
         println!(
-            "UPDATES_LOG: update_id={} chat_id={}: text={}",
-            u.update_id,
-            u.message.as_ref().map(|x| x.chat.id).unwrap_or(-1),
-            if u.message.is_some() && u.message.as_ref().unwrap().text.is_some() {
-                &*u.message.as_ref().unwrap().text.as_ref().unwrap()
-            } else {
-                "NONE"
+            "Reply to {} in {} is stale, dropping it",
+            stale.key, stale.chat_id
+        );
+        state.finish(stale.item.update_id);
+    }
+    while let Some(reply) = outbox.pop_ready(Instant::now()) {
+        let (chat_id, message_id) = (reply.chat_id, reply.key);
+        let result = match &reply.item.action {
+            Action::Send(text) => client
+                .send_message(&SendMessage {
+                    chat_id,
+                    text,
+                    reply_to_message_id: Some(message_id),
+                })
+                .map(|x| state.set_reply(chat_id, message_id, x.message_id, text)),
+            Action::Edit { reply_id, text } => client
+                .edit_message_text(&EditMessageText {
+                    chat_id,
+                    message_id: *reply_id,
+                    text,
+                })
+                .map(|_| state.set_reply(chat_id, message_id, *reply_id, text)),
+            Action::Delete { reply_id } => client
+                .delete_message(&DeleteMessage {
+                    chat_id,
+                    message_id: *reply_id,
+                })
+                .map(|_| state.remove_reply(chat_id, message_id)),
+        };
+        let e = match result {
+            Ok(()) => {
+                state.finish(reply.item.update_id);
+                continue;
             }
+            Err(e) => e,
+        };
+        if !e.is_retryable() {
+            println!("Replying in {} failed permanently: {}", reply.chat_id, e);
+            state.finish(reply.item.update_id);
+            continue;
+        }
+        let attempt = reply.attempts + 1;
+        if attempt == SEND_ATTEMPTS {
+            println!(
+                "Replying in {} failed {} times, giving up: {}",
+                reply.chat_id, attempt, e
+            );
+            state.finish(reply.item.update_id);
+            continue;
+        }
+        let delay = e
+            .retry_after()
+            .unwrap_or_else(|| Duration::from_secs(attempt.into()));
+        println!(
+            "Replying in {} failed, retrying in {:?}: {}",
+            reply.chat_id, delay, e
         );
+        outbox.retry(reply, delay, Instant::now());
     }
 }
 
-fn reply_to_message(chat_id: &str, message_id: &str, text: &str, token: &str) {
-    reqwest::blocking::Client::new()
-        .post(&*format!(
-            "https://api.telegram.org/bot{}/sendMessage",
-            token
-        ))
-        .form(&[
-            ("chat_id", chat_id),
-            ("reply_to_message_id", message_id),
-            ("text", text),
-        ])
-        .send()
-        .unwrap();
+/// Where the text following `command`, or `command@bot_name`, starts. In a
+/// group, commands addressed to other bots are theirs.
+fn command_argument(text: &str, command: &str, bot_name: &str) -> Option<usize> {
+    let rest = text.strip_prefix(command)?;
+    let command_length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let addressee = &rest[..command_length];
+    if !addressee.is_empty()
+        && addressee.strip_prefix('@').map(str::to_lowercase) != Some(bot_name.to_lowercase())
+    {
+        return None;
+    }
+    let end = command.len() + command_length;
+    Some(text.len() - text[end..].trim_start().len())
 }
 
-fn get_available_updates(
-    token: &str,
-    last_confirmed: &i64,
-) -> Result<serde_json::Value, Box<dyn Error>> {
-    let updates_url = &*format!("https://api.telegram.org/bot{}/getUpdates", token);
-    let updates = reqwest::blocking::Client::new()
-        .post(updates_url)
-        .form(&[("offset", *last_confirmed + 1)])
-        .send()?
-        .json()?;
-    Ok(updates)
+fn is_chat_admin(
+    chat: &Chat,
+    user: Option<&User>,
+    client: &Client,
+) -> Result<bool, Box<dyn Error>> {
+    if chat.kind.as_deref() == Some("private") {
+        return Ok(true);
+    }
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(false),
+    };
+    let member = client.get_chat_member(&GetChatMember {
+        chat_id: chat.id,
+        user_id: user.id,
+    })?;
+    Ok(member.status == "creator" || member.status == "administrator")
 }
 
-fn get_and_process_updates(
-    words: &HashSet<String>,
-    last_confirmed: &mut i64,
-    token: &str,
-) -> Result<(), Box<dyn Error>> {
-    let updates = get_available_updates(&*token, &last_confirmed)?;
-    match &updates["ok"].as_bool() {
-        Some(true) => Ok(true),
-        Some(false) => Err("Ok from telegram api is false"),
-        None => Err("No ok field in response from telegram api"),
-    }?;
-    let updates: Vec<Update> = serde_json::from_value(updates["result"].clone())?;
-    log_updates(&updates);
-    let last_update_id = updates.last().map(|x| x.update_id);
-    if last_update_id.is_some() {
-        *last_confirmed = last_update_id.unwrap();
+fn settings_command(
+    message: &Message,
+    argument: &str,
+    chat_settings: &mut ChatSettings,
+    client: &Client,
+) -> String {
+    let chat_id = message.chat.id;
+    let arguments: Vec<&str> = argument.split_whitespace().collect();
+    if !arguments.is_empty() {
+        match is_chat_admin(&message.chat, message.from.as_ref(), client) {
+            Ok(true) => (),
+            Ok(false) => return String::from("Only chat admins can change settings."),
+            Err(e) => {
+                // The error may carry internals such as request URLs, which
+                // must not reach the chat.
+                println!("Checking admin status in {} failed: {}", chat_id, e);
+                return String::from("Couldn't check whether you are an admin, try again later.");
+            }
+        }
     }
-    for u in updates {
-        let message_id = u.message.as_ref().map(|x| x.message_id);
-        let chat_id = u.message.as_ref().map(|x| x.chat.id);
-        let text = u.message.and_then(|x| x.text.map(|y| y.to_lowercase()));
-        let ratio = text.as_ref().map(|x| get_russians_ratio(&*x, &words));
-        let threshold = 0.5;
-        if ratio.unwrap_or(-1.0) > threshold {
-            let translated = fix_layout(&*text.unwrap().to_lowercase());
-            let message_id = message_id.unwrap();
-            let chat_id = chat_id.unwrap();
-            reply_to_message(
-                &*chat_id.to_string(),
-                &*message_id.to_string(),
-                &*translated,
-                token,
-            );
+    let changed = match arguments.as_slice() {
+        [] => Ok(()),
+        ["reset"] => chat_settings.reset(chat_id),
+        [name, value] => chat_settings.set(chat_id, name, value),
+        _ => Err(SettingsError::Invalid(String::from(
+            "expected a setting name and a value",
+        ))),
+    };
+    let settings = chat_settings.get(chat_id);
+    let current = format!(
+        "threshold {}, min_words {}, min_length {}, min_known_letters {}",
+        settings.threshold, settings.min_words, settings.min_length, settings.min_known_letters
+    );
+    match changed {
+        Ok(()) => format!(
+            "{}\nChange one with {} name value or go back to the defaults with {} reset. \
+             Names are {}.",
+            current,
+            SETTINGS_COMMAND,
+            SETTINGS_COMMAND,
+            SETTING_NAMES.join(", ")
+        ),
+        Err(SettingsError::Invalid(e)) => format!("{}\n{}", e, current),
+        Err(SettingsError::Save(e)) => {
+            // Like admin checks, I/O errors must not reach the chat.
+            println!("Saving the settings of {} failed: {}", chat_id, e);
+            format!("Couldn't save the settings, try again later.\n{}", current)
         }
     }
-    Ok(())
 }
 
-fn build_words(filename: &str) -> Result<HashSet<String>, Box<dyn Error>> {
-    let mut file = File::open(filename)?;
-    let mut buf = String::new();
-    file.read_to_string(&mut buf)?;
-    Ok(HashSet::from_iter(
-        buf.split("\n")
-            .filter(|s| !s.is_empty())
-            .map(|s| String::from(s)),
-    ))
+fn detect_message(
+    text: &str,
+    entities: &[MessageEntity],
+    corrector: &Corrector,
+    settings: &Settings,
+) -> Detection {
+    let detection = detect(
+        text,
+        &entities::protected_ranges(text, entities),
+        corrector,
+        settings,
+    );
+    println!(
+        "confidence {:.2}: {:?}",
+        detection.confidence, detection.reason
+    );
+    detection
+}
+
+/// The reply to a message: its corrected text or caption, or the answer to a
+/// command.
+fn respond(
+    message: Message,
+    corrector: &Corrector,
+    chat_settings: &mut ChatSettings,
+    client: &Client,
+    bot_name: &str,
+) -> Option<String> {
+    let text = message.text_or_caption()?;
+    let settings = chat_settings.get(message.chat.id);
+    if let Some(argument) = command_argument(text, SETTINGS_COMMAND, bot_name) {
+        return Some(settings_command(
+            &message,
+            &text[argument..],
+            chat_settings,
+            client,
+        ));
+    }
+    let entities = message.text_or_caption_entities();
+    let command_end = match command_argument(text, WHY_COMMAND, bot_name) {
+        Some(command_end) => command_end,
+        None => return detect_message(text, entities, corrector, &settings).corrected,
+    };
+    if command_end < text.len() {
+        let detection = detect(
+            &text[command_end..],
+            &entities::protected_ranges(text, entities)
+                .into_iter()
+                .filter(|x| x.start >= command_end)
+                .map(|x| x.start - command_end..x.end - command_end)
+                .collect::<Vec<_>>(),
+            corrector,
+            &settings,
+        );
+        return Some(detection.explain(corrector));
+    }
+    match message.reply_to_message {
+        Some(replied) => {
+            let text = replied.text_or_caption()?;
+            let entities = replied.text_or_caption_entities();
+            Some(detect_message(text, entities, corrector, &settings).explain(corrector))
+        }
+        None => Some(format!(
+            "Reply {} to a message, or send {} followed by text, to see why it \
+             would or wouldn't be corrected.",
+            WHY_COMMAND, WHY_COMMAND
+        )),
+    }
 }
 
 fn read_token(filename: &str) -> Result<String, Box<dyn Error>> {
@@ -184,26 +422,509 @@ fn read_token(filename: &str) -> Result<String, Box<dyn Error>> {
     Ok(buf)
 }
 
+/// A webhook secret token: 32 random hex digits.
+fn random_secret() -> Result<String, Box<dyn Error>> {
+    let mut bytes = [0; 16];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|x| format!("{:02x}", x)).collect())
+}
+
+/// The path part of `url`, which the webhook is served at.
+fn url_path(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |x| x.1);
+    let path = rest.find('/').map_or("/", |x| &rest[x..]);
+    path.split('?').next().unwrap_or(path)
+}
+
 fn usage(exec_name: &str) {
-    println!("Usage: {} words_file token_file", exec_name);
+    println!(
+        "Usage: {} --language code[=dictionary]... [--layout code=layout]... [--xkb-dir dir] \
+         [--detector dictionary|ngram|combined] [--corpus code=corpus_file]... \
+         [--ngram-model code=model_file]... [--typo-distance n] [--typo-words code=words_file]... \
+         [--correct-spelling] \
+         [--threshold ratio] [--min-words n] [--min-length n] [--min-known-letters n] \
+         [--chat-settings file] [--state file] [--api-url url] [--webhook url [--webhook-listen address] \
+         [--webhook-secret token] [--webhook-certificate pem_file]] token_file\n\
+         \n\
+         Languages are en, ru, uk, be and kk; at least two are needed. A layout is\n\
+         either a layout file or xkb:name(variant), e.g. xkb:ru(phonetic), looked\n\
+         up in the XKB symbols directory. A dictionary is a word list, an .fst\n\
+         file built by layout-corrector-train, which is memory-mapped, a .morph\n\
+         file of lemmas and inflection paradigms or a Hunspell .dic file with its\n\
+         .aff file next to it. The ngram and combined detectors use the models\n\
+         built by layout-corrector-train or train them at startup from plain-text\n\
+         corpora; every language needs one.\n\
+         \n\
+         With --typo-distance, converted words up to that many edits away from a\n\
+         dictionary word count as known; --correct-spelling also replaces them\n\
+         with that word in the reply. The typo index copies every word it holds\n\
+         and many variants of it, so it holds only the first 100000 words of the\n\
+         frequency-ranked list --typo-words gives for every language with a\n\
+         dictionary, such as the .words file of the trainer.\n\
+         \n\
+         A message is corrected when it has at least --min-words words (default\n\
+         1) and --min-length letters (default 0), its words that convert into\n\
+         known ones have at least --min-known-letters letters (default 3) and\n\
+         they make up more than --threshold (default 0.5) of the letters of the\n\
+         words. Single letters never count. Chat admins can override\n\
+         these for their chat with /settings; the overrides are kept in the\n\
+         --chat-settings file (default chat-settings.json).\n\
+         \n\
+         Which updates have been answered, and the bot's latest replies, are kept\n\
+         in the --state file (default bot-state.json), so that after a restart no\n\
+         update is lost or answered twice and edited messages still get their\n\
+         replies edited.\n\
+         \n\
+         --api-url points the bot at a local Bot API server instead of\n\
+         https://api.telegram.org.\n\
+         \n\
+         The bot polls for updates unless --webhook gives the public HTTPS URL\n\
+         to receive them at. It then serves plain HTTP on --webhook-listen\n\
+         (default 127.0.0.1:8080), behind a reverse proxy that terminates TLS,\n\
+         and accepts only requests bearing --webhook-secret, random by default.\n\
+         --webhook-certificate uploads the proxy's self-signed certificate.",
+        exec_name
+    );
+}
+
+struct Args {
+    languages: Vec<(String, Option<String>)>,
+    layouts: HashMap<String, String>,
+    xkb_dir: String,
+    detector: String,
+    corpora: Vec<(String, String)>,
+    ngram_models: Vec<(String, String)>,
+    typo_distance: usize,
+    typo_words: HashMap<String, String>,
+    correct_spelling: bool,
+    settings: Settings,
+    chat_settings: String,
+    state: String,
+    api_url: String,
+    webhook: Option<String>,
+    webhook_listen: String,
+    webhook_secret: Option<String>,
+    webhook_certificate: Option<String>,
+    positional: Vec<String>,
+}
+
+fn parse_key_value(arg: &str) -> Option<(String, String)> {
+    let mut parts = arg.splitn(2, '=');
+    Some((String::from(parts.next()?), String::from(parts.next()?)))
+}
+
+fn parse_args(argv: &[String]) -> Option<Args> {
+    let mut args = Args {
+        languages: Vec::new(),
+        layouts: HashMap::new(),
+        xkb_dir: String::from(xkb::DEFAULT_SYMBOLS_DIR),
+        detector: String::from("dictionary"),
+        corpora: Vec::new(),
+        ngram_models: Vec::new(),
+        typo_distance: 0,
+        typo_words: HashMap::new(),
+        correct_spelling: false,
+        settings: Settings::default(),
+        chat_settings: String::from("chat-settings.json"),
+        state: String::from("bot-state.json"),
+        api_url: String::from(telegram::DEFAULT_BASE_URL),
+        webhook: None,
+        webhook_listen: String::from("127.0.0.1:8080"),
+        webhook_secret: None,
+        webhook_certificate: None,
+        positional: Vec::new(),
+    };
+    let mut iter = argv.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--language" => {
+                let language = iter.next()?;
+                args.languages.push(match parse_key_value(language) {
+                    Some((code, words)) => (code, Some(words)),
+                    None => (language.clone(), None),
+                });
+            }
+            "--layout" => {
+                let (code, layout) = parse_key_value(iter.next()?)?;
+                args.layouts.insert(code, layout);
+            }
+            "--xkb-dir" => args.xkb_dir = iter.next()?.clone(),
+            "--detector" => args.detector = iter.next()?.clone(),
+            "--corpus" => args.corpora.push(parse_key_value(iter.next()?)?),
+            "--ngram-model" => args.ngram_models.push(parse_key_value(iter.next()?)?),
+            "--typo-distance" => args.typo_distance = iter.next()?.parse().ok()?,
+            "--typo-words" => {
+                let (code, filename) = parse_key_value(iter.next()?)?;
+                args.typo_words.insert(code, filename);
+            }
+            "--correct-spelling" => args.correct_spelling = true,
+            "--threshold" => args.settings.threshold = iter.next()?.parse().ok()?,
+            "--min-words" => args.settings.min_words = iter.next()?.parse().ok()?,
+            "--min-length" => args.settings.min_length = iter.next()?.parse().ok()?,
+            "--min-known-letters" => args.settings.min_known_letters = iter.next()?.parse().ok()?,
+            "--chat-settings" => args.chat_settings = iter.next()?.clone(),
+            "--state" => args.state = iter.next()?.clone(),
+            "--api-url" => args.api_url = iter.next()?.clone(),
+            "--webhook" => args.webhook = Some(iter.next()?.clone()),
+            "--webhook-listen" => args.webhook_listen = iter.next()?.clone(),
+            "--webhook-secret" => args.webhook_secret = Some(iter.next()?.clone()),
+            "--webhook-certificate" => args.webhook_certificate = Some(iter.next()?.clone()),
+            _ if arg.starts_with("--") => return None,
+            _ => args.positional.push(arg.clone()),
+        }
+    }
+    Some(args)
+}
+
+fn load_layout(
+    code: &str,
+    spec: Option<&String>,
+    xkb_dir: &SymbolsDir,
+) -> Result<Layout, Box<dyn Error>> {
+    match spec {
+        Some(spec) => match spec.strip_prefix("xkb:") {
+            Some(name) => xkb_dir.load(name),
+            None => Layout::load(spec),
+        },
+        None => language::default_layout(code)
+            .ok_or_else(|| format!("no layout known for language '{}'", code).into()),
+    }
+}
+
+fn build_ngram_detector(args: &Args) -> Result<NgramDetector, Box<dyn Error>> {
+    let mut models = HashMap::new();
+    for (code, model_filename) in &args.ngram_models {
+        models.insert(code.clone(), NgramModel::load(model_filename)?);
+        println!("ngram model for {} loaded", code);
+    }
+    for (code, corpus_filename) in &args.corpora {
+        let model = NgramModel::train(&std::fs::read_to_string(corpus_filename)?);
+        println!("ngram model for {} trained", code);
+        models.insert(code.clone(), model);
+    }
+    // A language without a model would never recognise the words typed in
+    // it, so every conversion out of it would win.
+    if let Some((code, _)) = args.languages.iter().find(|x| !models.contains_key(&x.0)) {
+        return Err(format!(
+            "no ngram model for {}: give it --ngram-model or --corpus",
+            code
+        )
+        .into());
+    }
+    Ok(NgramDetector::new(models, NgramDetector::DEFAULT_MARGIN))
+}
+
+fn build_detector(args: &Args) -> Result<Box<dyn Detector>, Box<dyn Error>> {
+    match args.detector.as_str() {
+        "dictionary" => Ok(Box::new(DictionaryDetector)),
+        "ngram" => Ok(Box::new(build_ngram_detector(args)?)),
+        "combined" => Ok(Box::new(CombinedDetector::new(vec![
+            (1.0, Box::new(DictionaryDetector)),
+            (1.0, Box::new(build_ngram_detector(args)?)),
+        ]))),
+        detector => Err(format!("unknown detector '{}'", detector).into()),
+    }
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let argv: Vec<_> = std::env::args().collect();
-    if argv.len() != 3 {
-        usage(&argv[0]);
-        std::process::exit(1);
+    let args = match parse_args(&argv) {
+        Some(args) if args.positional.len() == 1 && args.languages.len() >= 2 => args,
+        _ => {
+            usage(&argv[0]);
+            std::process::exit(1);
+        }
+    };
+    let xkb_dir = SymbolsDir::new(&args.xkb_dir);
+    let mut languages = Vec::new();
+    for (code, words_filename) in &args.languages {
+        let layout = load_layout(code, args.layouts.get(code), &xkb_dir)?;
+        println!("language {}: layout {}", code, layout.name());
+        let words = match words_filename {
+            Some(words_filename) => load_dictionary(words_filename)?,
+            None => Box::new(HashSet::new()),
+        };
+        let mut language = Language::new(code, layout, words);
+        // Indexing a whole dictionary would take far more memory than the
+        // dictionary itself, so only a bounded list is.
+        match (args.typo_distance, args.typo_words.get(code)) {
+            (0, _) => (),
+            (_, Some(filename)) => {
+                let words = ranked_words(filename, MAX_TYPO_WORDS)?;
+                language.index_typos(words, args.typo_distance);
+                println!("typo index for {} built", code);
+            }
+            (_, None) if words_filename.is_some() => {
+                return Err(format!("--typo-distance needs --typo-words for {}", code).into());
+            }
+            (_, None) => (),
+        }
+        languages.push(language);
     }
-    let (words_filename, token_filename) = (&argv[1], &argv[2]);
-    let words = build_words(&*words_filename)?;
-    let token = read_token(&*token_filename)?;
+    let mut corrector = Corrector::new(languages, build_detector(&args)?);
+    corrector.correct_spelling = args.correct_spelling;
+    let mut chat_settings = ChatSettings::load(&args.chat_settings, args.settings)?;
+    let token = read_token(&args.positional[0])?;
+    let transport = HttpTransport::with_timeout(REQUEST_TIMEOUT)?;
+    let client = Client::new(Box::new(transport), &args.api_url, token.trim());
+    let bot_name = client.get_me()?.username.unwrap_or_default();
+    println!("bot @{}", bot_name);
     println!("words array built!");
-    let mut last_confirmed = 0;
+    let mut outbox = Outbox::new(Outbox::<Reply>::DEFAULT_MAX_AGE, Instant::now());
+    let mut state = UpdateState::load(&args.state)?;
+    let url = match &args.webhook {
+        Some(url) => url,
+        None => {
+            if let Err(e) = client.delete_webhook() {
+                println!("Deleting the webhook failed: {}", e);
+            }
+            poll(
+                &client,
+                &bot_name,
+                &corrector,
+                &mut chat_settings,
+                &mut outbox,
+                &mut state,
+            );
+        }
+    };
+    let secret = match &args.webhook_secret {
+        Some(secret) => secret.clone(),
+        None => random_secret()?,
+    };
+    let server = WebhookServer::bind(&args.webhook_listen, url_path(url), &secret)?;
+    let certificate = match &args.webhook_certificate {
+        Some(filename) => Some(std::fs::read(filename)?),
+        None => None,
+    };
+    client.set_webhook(
+        &SetWebhook {
+            url: url.clone(),
+            secret_token: Some(secret),
+            allowed_updates: Some(allowed_updates()),
+        },
+        certificate.as_deref(),
+    )?;
+    println!("webhook {} set, listening on {}", url, args.webhook_listen);
+    loop {
+        if let Some(update) = server.next_update(receive_timeout(&mut outbox)) {
+            process_updates(
+                vec![update],
+                &client,
+                &bot_name,
+                &corrector,
+                &mut chat_settings,
+                &mut outbox,
+                &mut state,
+            );
+        }
+        send_replies(&client, &mut outbox, &mut state);
+        save_state(&mut state);
+    }
+}
+
+fn poll(
+    client: &Client,
+    bot_name: &str,
+    corrector: &Corrector,
+    chat_settings: &mut ChatSettings,
+    outbox: &mut Outbox<Reply>,
+    state: &mut UpdateState,
+) -> ! {
+    let mut backoff = MIN_BACKOFF;
     loop {
-        std::thread::sleep(std::time::Duration::from_millis(1000));
-        match get_and_process_updates(&words, &mut last_confirmed, &*token) {
-            Ok(_) => (),
-            Err(e) => println!("Processing updates failed: {}", e),
+        match get_and_process_updates(client, bot_name, corrector, chat_settings, outbox, state) {
+            Ok(only_repeats) => {
+                backoff = MIN_BACKOFF;
+                if only_repeats {
+                    std::thread::sleep(receive_timeout(outbox).min(REPEAT_POLL_INTERVAL));
+                }
+            }
+            Err(e) => {
+                println!(
+                    "Processing updates failed, retrying in {:?}: {}",
+                    backoff, e
+                );
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+        send_replies(client, outbox, state);
+        save_state(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use layout_corrector::telegram::Transport;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Requests = Rc<RefCell<Vec<(String, serde_json::Value)>>>;
+
+    /// Records the requests and answers them as the Bot API would.
+    struct FakeServer {
+        requests: Requests,
+    }
+
+    impl Transport for FakeServer {
+        fn post(
+            &self,
+            url: &str,
+            _content_type: &str,
+            body: Vec<u8>,
+        ) -> Result<String, Box<dyn Error>> {
+            let method = url.rsplit('/').next().unwrap_or_default();
+            self.requests
+                .borrow_mut()
+                .push((String::from(method), serde_json::from_slice(&body)?));
+            Ok(String::from(match method {
+                "deleteMessage" => r#"{"ok":true,"result":true}"#,
+                _ => r#"{"ok":true,"result":{"message_id":100,"chat":{"id":-100},"date":0}}"#,
+            }))
+        }
+    }
+
+    struct Bot {
+        client: Client,
+        corrector: Corrector,
+        chat_settings: ChatSettings,
+        outbox: Outbox<Reply>,
+        state: UpdateState,
+        requests: Requests,
+    }
+
+    impl Bot {
+        fn new(name: &str) -> Bot {
+            let path = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+            let path = path.to_str().unwrap();
+            let language = |code: &str, words: &[&str]| {
+                let words: HashSet<String> = words.iter().map(|x| String::from(*x)).collect();
+                Language::new(
+                    code,
+                    language::default_layout(code).unwrap(),
+                    Box::new(words),
+                )
+            };
+            let requests = Requests::default();
+            Bot {
+                client: Client::new(
+                    Box::new(FakeServer {
+                        requests: requests.clone(),
+                    }),
+                    "http://localhost",
+                    "1:abc",
+                ),
+                corrector: Corrector::new(
+                    vec![
+                        language("en", &["hello", "world"]),
+                        language("ru", &["привет", "мир"]),
+                    ],
+                    Box::new(DictionaryDetector),
+                ),
+                chat_settings: ChatSettings::load(
+                    &format!("{}-settings.json", path),
+                    Settings::default(),
+                )
+                .unwrap(),
+                outbox: Outbox::new(Outbox::<Reply>::DEFAULT_MAX_AGE, Instant::now()),
+                state: UpdateState::load(&format!("{}-state.json", path)).unwrap(),
+                requests,
+            }
         }
+
+        /// Processes a message, or its edit, with the text.
+        fn receive(&mut self, update_id: i64, message_id: i64, edited: bool, text: &str) {
+            let update: Update = serde_json::from_value(serde_json::json!({
+                "update_id": update_id,
+                (if edited { "edited_message" } else { "message" }): {
+                    "message_id": message_id,
+                    "chat": {"id": -100, "type": "group"},
+                    "date": 0,
+                    "text": text,
+                },
+            }))
+            .unwrap();
+            process_updates(
+                vec![update],
+                &self.client,
+                "layout_bot",
+                &self.corrector,
+                &mut self.chat_settings,
+                &mut self.outbox,
+                &mut self.state,
+            );
+        }
+
+        /// Sends the queued replies, returning the methods called with the
+        /// text they sent, or the message they deleted.
+        fn send(&mut self) -> Vec<(String, serde_json::Value)> {
+            send_replies(&self.client, &mut self.outbox, &mut self.state);
+            self.requests
+                .borrow_mut()
+                .drain(..)
+                .map(|(method, body)| {
+                    let key = if method == "deleteMessage" {
+                        "message_id"
+                    } else {
+                        "text"
+                    };
+                    (method, body[key].clone())
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn commands_for_other_bots_are_ignored() {
+        assert_eq!(command_argument("/why", "/why", "layout_bot"), Some(4));
+        assert_eq!(command_argument("/why  vbh", "/why", "layout_bot"), Some(6));
+        assert_eq!(
+            command_argument("/why@Layout_Bot vbh", "/why", "layout_bot"),
+            Some(16)
+        );
+        assert_eq!(
+            command_argument("/why@other_bot vbh", "/why", "layout_bot"),
+            None
+        );
+        assert_eq!(command_argument("/whynot", "/why", "layout_bot"), None);
+    }
+
+    fn call(method: &str, detail: serde_json::Value) -> (String, serde_json::Value) {
+        (String::from(method), detail)
+    }
+
+    #[test]
+    fn edits_update_the_reply() {
+        let mut bot = Bot::new("edits-update-the-reply");
+        bot.receive(1, 1, false, "ghbdtn");
+        assert_eq!(bot.send(), [call("sendMessage", "привет".into())]);
+        bot.receive(2, 1, true, "ghbdtn vbh");
+        assert_eq!(bot.send(), [call("editMessageText", "привет мир".into())]);
+        // Nothing changes, so nothing is called.
+        bot.receive(3, 1, true, "ghbdtn vbh");
+        assert_eq!(bot.send(), []);
+        bot.receive(4, 1, true, "hello world");
+        assert_eq!(bot.send(), [call("deleteMessage", 100.into())]);
+        assert!((1..=4).all(|x| bot.state.is_done(x)));
+    }
+
+    #[test]
+    fn an_edit_replaces_the_queued_reply() {
+        let mut bot = Bot::new("edit-replaces-queued-reply");
+        bot.receive(1, 1, false, "ghbdtn");
+        bot.receive(2, 1, true, "ghbdtn vbh");
+        assert!(bot.state.is_done(1));
+        assert_eq!(bot.send(), [call("sendMessage", "привет мир".into())]);
+        // Edited into needing no reply while the reply is queued.
+        bot.receive(3, 2, false, "ghbdtn");
+        bot.receive(4, 2, true, "hello");
+        assert_eq!(bot.send(), []);
+        // Edited into needing a reply it didn't have.
+        bot.receive(5, 3, false, "hello");
+        bot.receive(6, 3, true, "vbh");
+        assert_eq!(bot.send(), [call("sendMessage", "мир".into())]);
+        assert!((1..=6).all(|x| bot.state.is_done(x)));
     }
 }
@@ -0,0 +1,217 @@
+use crate::dictionary::Dictionary;
+use std::collections::HashMap;
+use std::error::Error;
+
+// First line of a morphology file.
+const MORPH_HEADER: &str = "# layout-corrector morphology v1";
+
+// Written for an empty ending in morphology files.
+const EMPTY_ENDING: &str = "-";
+
+/// How the forms of a word are built from its lemma: `strip` is cut off the
+/// end of the lemma and each of `endings` is appended to what is left.
+struct Paradigm {
+    name: String,
+    strip: String,
+    endings: Vec<String>,
+}
+
+/// Recognises every inflected form of its lemmas instead of storing each
+/// form separately.
+///
+/// The file format is line based. `paradigm name strip ending...` defines a
+/// paradigm, any other line is a lemma followed by the names of its
+/// paradigms. `-` stands for an empty ending:
+///
+/// ```text
+/// paradigm noun-hard - - а у ом е ы ов ам ами ах ик
+/// paradigm noun-a а а ы е у ой - ам ами ах
+/// привет noun-hard
+/// мама noun-a
+/// ```
+pub struct MorphologyDictionary {
+    paradigms: Vec<Paradigm>,
+    lemmas: HashMap<String, Vec<usize>>,
+    // Every ending of every paradigm, to the paradigms that use it.
+    endings: HashMap<String, Vec<usize>>,
+    longest_ending: usize,
+}
+
+fn parse_ending(ending: &str) -> String {
+    if ending == EMPTY_ENDING {
+        String::new()
+    } else {
+        String::from(ending)
+    }
+}
+
+impl MorphologyDictionary {
+    pub fn parse(src: &str) -> Result<MorphologyDictionary, Box<dyn Error>> {
+        let mut lines = src.lines().enumerate();
+        if lines.next().map(|x| x.1) != Some(MORPH_HEADER) {
+            return Err("not a morphology file".into());
+        }
+        let mut dictionary = MorphologyDictionary {
+            paradigms: Vec::new(),
+            lemmas: HashMap::new(),
+            endings: HashMap::new(),
+            longest_ending: 0,
+        };
+        let mut paradigm_ids = HashMap::new();
+        for (line_number, line) in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [] => (),
+                [first, ..] if first.starts_with('#') => (),
+                ["paradigm", name, strip, endings @ ..] if !endings.is_empty() => {
+                    let id = dictionary.paradigms.len();
+                    for ending in endings.iter().map(|x| parse_ending(x)) {
+                        dictionary.longest_ending =
+                            dictionary.longest_ending.max(ending.chars().count());
+                        let paradigms = dictionary.endings.entry(ending).or_default();
+                        if !paradigms.contains(&id) {
+                            paradigms.push(id);
+                        }
+                    }
+                    dictionary.paradigms.push(Paradigm {
+                        name: String::from(*name),
+                        strip: parse_ending(strip),
+                        endings: endings.iter().map(|x| parse_ending(x)).collect(),
+                    });
+                    paradigm_ids.insert(String::from(*name), id);
+                }
+                [lemma, paradigms @ ..] if !paradigms.is_empty() => {
+                    let mut ids = Vec::new();
+                    for name in paradigms {
+                        let id = paradigm_ids.get(*name).ok_or_else(|| {
+                            format!(
+                                "morphology line {}: unknown paradigm '{}'",
+                                line_number + 1,
+                                name
+                            )
+                        })?;
+                        if !lemma.ends_with(&dictionary.paradigms[*id].strip) {
+                            return Err(format!(
+                                "morphology line {}: '{}' doesn't end with '{}'",
+                                line_number + 1,
+                                lemma,
+                                dictionary.paradigms[*id].strip
+                            )
+                            .into());
+                        }
+                        ids.push(*id);
+                    }
+                    dictionary
+                        .lemmas
+                        .entry(String::from(*lemma))
+                        .or_default()
+                        .extend(ids);
+                }
+                _ => return Err(format!("morphology line {}: malformed", line_number + 1).into()),
+            }
+        }
+        Ok(dictionary)
+    }
+
+    pub fn load(filename: &str) -> Result<MorphologyDictionary, Box<dyn Error>> {
+        MorphologyDictionary::parse(&std::fs::read_to_string(filename)?)
+    }
+
+    /// The lemma `word` is a form of, together with the name of the paradigm
+    /// that produces it.
+    pub fn analyze(&self, word: &str) -> Option<(String, &str)> {
+        let chars: Vec<char> = word.chars().collect();
+        for ending_length in 0..=self.longest_ending.min(chars.len()) {
+            let split = chars.len() - ending_length;
+            let ending: String = chars[split..].iter().collect();
+            for id in self.endings.get(&ending).into_iter().flatten() {
+                let paradigm = &self.paradigms[*id];
+                let mut lemma: String = chars[..split].iter().collect();
+                lemma.push_str(&paradigm.strip);
+                let is_lemma_of_paradigm = self
+                    .lemmas
+                    .get(&lemma)
+                    .map(|x| x.contains(id))
+                    .unwrap_or(false);
+                if is_lemma_of_paradigm {
+                    return Some((lemma, &paradigm.name));
+                }
+            }
+        }
+        None
+    }
+
+    fn forms(&self) -> impl Iterator<Item = String> + '_ {
+        self.lemmas.iter().flat_map(move |(lemma, ids)| {
+            ids.iter().flat_map(move |id| {
+                let paradigm = &self.paradigms[*id];
+                let stem = &lemma[..lemma.len() - paradigm.strip.len()];
+                paradigm
+                    .endings
+                    .iter()
+                    .map(move |x| format!("{}{}", stem, x))
+            })
+        })
+    }
+}
+
+impl Dictionary for MorphologyDictionary {
+    fn contains(&self, word: &str) -> bool {
+        self.analyze(word).is_some()
+    }
+
+    fn words_with_prefix(&self, prefix: &str, limit: usize) -> Vec<String> {
+        let mut words: Vec<String> = self.forms().filter(|x| x.starts_with(prefix)).collect();
+        words.sort();
+        words.dedup();
+        words.truncate(limit);
+        words
+    }
+
+    fn len(&self) -> usize {
+        let mut forms: Vec<String> = self.forms().collect();
+        forms.sort();
+        forms.dedup();
+        forms.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MORPHOLOGY: &str = "# layout-corrector morphology v1
+paradigm noun-hard - - а у ом е ы ов ам ами ах ик
+paradigm noun-a а а ы е у ой - ам ами ах
+привет noun-hard
+мама noun-a
+";
+
+    #[test]
+    fn recognises_inflected_forms() {
+        let dictionary = MorphologyDictionary::parse(MORPHOLOGY).unwrap();
+        for word in &["привет", "приветами", "приветик", "мама", "мамой", "мам"]
+        {
+            assert!(dictionary.contains(word), "{}", word);
+        }
+        for word in &["приветой", "мамик", "прив"] {
+            assert!(!dictionary.contains(word), "{}", word);
+        }
+        assert_eq!(
+            dictionary.analyze("мамой"),
+            Some((String::from("мама"), "noun-a"))
+        );
+        assert_eq!(
+            dictionary.words_with_prefix("привета", 5),
+            vec!["привета", "приветам", "приветами", "приветах"]
+        );
+        assert_eq!(dictionary.len(), 20);
+    }
+
+    #[test]
+    fn rejects_unknown_paradigms() {
+        assert!(
+            MorphologyDictionary::parse("# layout-corrector morphology v1\nмама noun").is_err()
+        );
+    }
+}
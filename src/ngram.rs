@@ -0,0 +1,200 @@
+use crate::detector::Detector;
+use crate::language::{Language, INTRA_WORD};
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::error::Error;
+
+// Marks the start and the end of a word, so that trigrams also learn which
+// letters words tend to begin and end with.
+const BOUNDARY: char = ' ';
+
+// First line of a saved model.
+const MODEL_HEADER: &str = "# layout-corrector ngram model v1";
+
+/// Character trigram counts of one language.
+#[derive(Default)]
+pub struct NgramModel {
+    trigrams: HashMap<[char; 3], u32>,
+    contexts: HashMap<[char; 2], u32>,
+    letters: HashSet<char>,
+}
+
+/// The lowercased words of a plain-text corpus.
+pub fn corpus_words(corpus: &str) -> impl Iterator<Item = String> + '_ {
+    corpus
+        .split(|x: char| !x.is_alphabetic() && !INTRA_WORD.contains(&x))
+        .map(|x| x.trim_matches(INTRA_WORD))
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_lowercase())
+}
+
+fn padded(word: &str) -> Vec<char> {
+    let mut chars = vec![BOUNDARY, BOUNDARY];
+    chars.extend(word.chars());
+    chars.push(BOUNDARY);
+    chars
+}
+
+impl NgramModel {
+    pub fn new() -> NgramModel {
+        NgramModel::default()
+    }
+
+    /// Counts the trigrams of every word of a plain-text corpus.
+    pub fn train(corpus: &str) -> NgramModel {
+        let mut model = NgramModel::new();
+        for word in corpus_words(corpus) {
+            model.add_word(&word, 1);
+        }
+        model
+    }
+
+    /// Reads a model written by `save`.
+    pub fn parse(src: &str) -> Result<NgramModel, Box<dyn Error>> {
+        let mut lines = src.lines();
+        if lines.next() != Some(MODEL_HEADER) {
+            return Err("not an ngram model file".into());
+        }
+        let mut model = NgramModel::new();
+        for (line_number, line) in lines.enumerate() {
+            let mut fields = line.split('\t');
+            let trigram: Vec<char> = fields.next().unwrap_or("").chars().collect();
+            let count = fields.next().and_then(|x| x.parse().ok());
+            match (trigram.as_slice(), count) {
+                (&[a, b, c], Some(count)) => model.add_trigram([a, b, c], count),
+                _ => return Err(format!("ngram model line {}: malformed", line_number + 2).into()),
+            }
+        }
+        Ok(model)
+    }
+
+    pub fn load(filename: &str) -> Result<NgramModel, Box<dyn Error>> {
+        NgramModel::parse(&std::fs::read_to_string(filename)?)
+    }
+
+    /// Writes the trigram counts one per line, sorted so that training the
+    /// same corpus always gives the same file.
+    pub fn save(&self) -> String {
+        let mut trigrams: Vec<(String, u32)> = self
+            .trigrams
+            .iter()
+            .map(|(trigram, count)| (trigram.iter().collect(), *count))
+            .collect();
+        trigrams.sort();
+        let mut out = String::from(MODEL_HEADER);
+        out.push('\n');
+        for (trigram, count) in trigrams {
+            out.push_str(&format!("{}\t{}\n", trigram, count));
+        }
+        out
+    }
+
+    pub fn trigram_count(&self) -> usize {
+        self.trigrams.len()
+    }
+
+    pub fn add_word(&mut self, word: &str, count: u32) {
+        for trigram in padded(word).windows(3) {
+            self.add_trigram([trigram[0], trigram[1], trigram[2]], count);
+        }
+    }
+
+    pub fn add_trigram(&mut self, trigram: [char; 3], count: u32) {
+        self.letters.insert(trigram[2]);
+        *self.trigrams.entry(trigram).or_insert(0) += count;
+        *self.contexts.entry([trigram[0], trigram[1]]).or_insert(0) += count;
+    }
+
+    /// Average log10 probability of the word's trigrams, with add-one
+    /// smoothing for the unseen ones.
+    pub fn log_probability(&self, word: &str) -> f32 {
+        let chars = padded(word);
+        let trigrams = chars.windows(3);
+        let count = trigrams.len() as f32;
+        let alphabet_size = self.letters.len() as f32;
+        let total: f32 = trigrams
+            .map(|x| {
+                let trigram = self.trigrams.get(&[x[0], x[1], x[2]]).copied().unwrap_or(0);
+                let context = self.contexts.get(&[x[0], x[1]]).copied().unwrap_or(0);
+                ((trigram as f32 + 1.0) / (context as f32 + alphabet_size + 1.0)).log10()
+            })
+            .sum();
+        total / count
+    }
+
+    /// The log10 probability of a trigram whose context was never seen,
+    /// which is what `log_probability` gives a word of unseen letters.
+    pub fn unseen_log_probability(&self) -> f32 {
+        (1.0 / (self.letters.len() as f32 + 1.0)).log10()
+    }
+}
+
+/// Recognises words whose trigrams are likelier in the language's model than
+/// trigrams it has never seen. Smoothing gives those a fair probability, so
+/// any fixed floor would let gibberish through.
+pub struct NgramDetector {
+    models: HashMap<String, NgramModel>,
+    margin: f32,
+}
+
+impl NgramDetector {
+    /// How far above the unseen-trigram probability, in average log10
+    /// probability, a word must be to be recognised.
+    pub const DEFAULT_MARGIN: f32 = 0.0;
+
+    pub fn new(models: HashMap<String, NgramModel>, margin: f32) -> NgramDetector {
+        NgramDetector { models, margin }
+    }
+}
+
+impl Detector for NgramDetector {
+    fn score(&self, word: &str, language: &Language) -> f32 {
+        match self.models.get(&language.code) {
+            Some(model) => {
+                model.log_probability(word) - model.unseen_log_probability() - self.margin
+            }
+            None => f32::NEG_INFINITY,
+        }
+    }
+
+    fn name(&self) -> String {
+        String::from("ngram")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGLISH: &str = "hello world, this is the weather today. \
+                           the world is a wonderful place and we will help them there";
+    const RUSSIAN: &str = "привет мир, это погода сегодня. \
+                           мир прекрасен и мы поможем вам, привет всем друзьям";
+
+    #[test]
+    fn prefers_the_right_language() {
+        let (english, russian) = (NgramModel::train(ENGLISH), NgramModel::train(RUSSIAN));
+        assert!(english.log_probability("hello") > english.log_probability("ghbdtn"));
+        assert!(russian.log_probability("привет") > english.log_probability("ghbdtn"));
+        assert!(english.log_probability("world") > russian.log_probability("цщкдв"));
+    }
+
+    #[test]
+    fn keeps_apostrophes_and_hyphens_inside_words() {
+        let words: Vec<String> =
+            corpus_words("М'ясо -- по-русски, 'don't' - rock'n'roll-").collect();
+        assert_eq!(words, ["м'ясо", "по-русски", "don't", "rock'n'roll"]);
+    }
+
+    #[test]
+    fn saved_model_loads_back() {
+        let model = NgramModel::train(RUSSIAN);
+        let saved = model.save();
+        let loaded = NgramModel::parse(&saved).unwrap();
+        assert_eq!(loaded.save(), saved);
+        assert_eq!(
+            loaded.log_probability("привет"),
+            model.log_probability("привет")
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// Telegram's limits: about 30 messages a second overall, 20 a minute in a
+// group and one a second in a private chat.
+const GLOBAL_BURST: f64 = 30.0;
+const GLOBAL_RATE: f64 = 30.0;
+const GROUP_BURST: f64 = 3.0;
+const GROUP_RATE: f64 = 20.0 / 60.0;
+const PRIVATE_BURST: f64 = 1.0;
+const PRIVATE_RATE: f64 = 1.0;
+
+// A busy chat can't pile up more replies than this; the oldest go first.
+const MAX_QUEUED_PER_CHAT: usize = 10;
+
+/// Allows a burst of `capacity` actions and then `rate` actions a second.
+pub struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: f64, rate: f64, now: Instant) -> TokenBucket {
+        TokenBucket {
+            capacity,
+            rate,
+            tokens: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = self.updated.max(now);
+    }
+
+    /// How long until an action is allowed, zero if it is now.
+    pub fn wait(&mut self, now: Instant) -> Duration {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / self.rate)
+        }
+    }
+
+    /// Whether it has refilled, so that a new bucket would be just the same.
+    pub fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+
+    pub fn take(&mut self, now: Instant) {
+        self.refill(now);
+        self.tokens -= 1.0;
+    }
+}
+
+/// An item waiting in the outbox, with the chat it goes to and the key it
+/// is coalesced by, such as the message it replies to.
+pub struct Queued<T> {
+    pub chat_id: i64,
+    pub key: i64,
+    pub item: T,
+    /// How many times sending it has failed.
+    pub attempts: u32,
+    queued_at: Instant,
+}
+
+struct ChatState {
+    bucket: TokenBucket,
+    // Set when Telegram answered with 429 and retry_after.
+    paused_until: Option<Instant>,
+}
+
+impl ChatState {
+    fn wait(&mut self, now: Instant) -> Duration {
+        let paused = self
+            .paused_until
+            .map(|x| x.saturating_duration_since(now))
+            .unwrap_or_default();
+        paused.max(self.bucket.wait(now))
+    }
+
+    // Whether forgetting the chat changes nothing, as it would start afresh.
+    fn is_idle(&mut self, now: Instant) -> bool {
+        self.wait(now) == Duration::ZERO && self.bucket.is_full(now)
+    }
+}
+
+/// Outgoing messages queued under the global and per-chat rate limits, so
+/// that a busy group can't get the bot throttled or banned. Items that wait
+/// longer than `max_age` are dropped instead of being sent late.
+pub struct Outbox<T> {
+    queue: VecDeque<Queued<T>>,
+    global: TokenBucket,
+    chats: HashMap<i64, ChatState>,
+    max_age: Duration,
+}
+
+impl<T> Outbox<T> {
+    pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(120);
+
+    pub fn new(max_age: Duration, now: Instant) -> Outbox<T> {
+        Outbox {
+            queue: VecDeque::new(),
+            global: TokenBucket::new(GLOBAL_BURST, GLOBAL_RATE, now),
+            chats: HashMap::new(),
+            max_age,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Queues `item`, replacing a queued item of the same chat and key, which
+    /// it is a newer version of. Returns the item replaced, or dropped to make
+    /// room, if any.
+    pub fn push(&mut self, chat_id: i64, key: i64, item: T, now: Instant) -> Option<T> {
+        if let Some(queued) = self
+            .queue
+            .iter_mut()
+            .find(|x| x.chat_id == chat_id && x.key == key)
+        {
+            return Some(std::mem::replace(&mut queued.item, item));
+        }
+        let queued_in_chat = self.queue.iter().filter(|x| x.chat_id == chat_id).count();
+        let mut dropped = None;
+        if queued_in_chat >= MAX_QUEUED_PER_CHAT {
+            let oldest = self.queue.iter().position(|x| x.chat_id == chat_id);
+            dropped = self.queue.remove(oldest.unwrap()).map(|x| x.item);
+        }
+        self.queue.push_back(Queued {
+            chat_id,
+            key,
+            item,
+            attempts: 0,
+            queued_at: now,
+        });
+        dropped
+    }
+
+    /// Takes the queued item of the chat and key out of the queue.
+    pub fn remove(&mut self, chat_id: i64, key: i64) -> Option<T> {
+        let index = self
+            .queue
+            .iter()
+            .position(|x| x.chat_id == chat_id && x.key == key)?;
+        self.queue.remove(index).map(|x| x.item)
+    }
+
+    /// Removes and returns the items that waited too long to still be worth
+    /// sending. Also forgets the rate limits of idle chats with nothing
+    /// queued.
+    pub fn drop_stale(&mut self, now: Instant) -> Vec<Queued<T>> {
+        let max_age = self.max_age;
+        let (stale, fresh): (VecDeque<_>, _) = self
+            .queue
+            .drain(..)
+            .partition(|x| now.saturating_duration_since(x.queued_at) > max_age);
+        self.queue = fresh;
+        let queue = &self.queue;
+        self.chats.retain(|chat_id, chat| {
+            queue.iter().any(|x| x.chat_id == *chat_id) || !chat.is_idle(now)
+        });
+        stale.into()
+    }
+
+    fn chat(&mut self, chat_id: i64, now: Instant) -> &mut ChatState {
+        self.chats.entry(chat_id).or_insert_with(|| {
+            // Group and channel ids are negative.
+            let bucket = if chat_id < 0 {
+                TokenBucket::new(GROUP_BURST, GROUP_RATE, now)
+            } else {
+                TokenBucket::new(PRIVATE_BURST, PRIVATE_RATE, now)
+            };
+            ChatState {
+                bucket,
+                paused_until: None,
+            }
+        })
+    }
+
+    /// Takes the oldest item the rate limits allow to send now, counting it
+    /// as sent.
+    pub fn pop_ready(&mut self, now: Instant) -> Option<Queued<T>> {
+        if self.global.wait(now) > Duration::ZERO {
+            return None;
+        }
+        let mut waiting_chats = Vec::new();
+        for i in 0..self.queue.len() {
+            let chat_id = self.queue[i].chat_id;
+            if waiting_chats.contains(&chat_id) {
+                continue;
+            }
+            let chat = self.chat(chat_id, now);
+            if chat.wait(now) > Duration::ZERO {
+                // Later items of the chat must not overtake this one.
+                waiting_chats.push(chat_id);
+                continue;
+            }
+            chat.bucket.take(now);
+            self.global.take(now);
+            return self.queue.remove(i);
+        }
+        None
+    }
+
+    /// How long until `pop_ready` may return an item, `None` if there are
+    /// none.
+    pub fn next_ready_in(&mut self, now: Instant) -> Option<Duration> {
+        let chat_ids: Vec<i64> = self.queue.iter().map(|x| x.chat_id).collect();
+        let global_wait = self.global.wait(now);
+        chat_ids
+            .into_iter()
+            .map(|x| self.chat(x, now).wait(now))
+            .min()
+            .map(|x| x.max(global_wait))
+    }
+
+    /// Puts back an item that failed to send, at the front of its chat's
+    /// queue, and holds the chat back for `delay`, e.g. the `retry_after` of
+    /// a 429 answer.
+    pub fn retry(&mut self, mut queued: Queued<T>, delay: Duration, now: Instant) {
+        queued.attempts += 1;
+        let chat = self.chat(queued.chat_id, now);
+        chat.paused_until = Some(now + delay);
+        self.queue.push_front(queued);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_each_chat() {
+        let start = Instant::now();
+        let mut outbox = Outbox::new(Outbox::<&str>::DEFAULT_MAX_AGE, start);
+        for key in 0..5 {
+            outbox.push(-100, key, "group", start);
+        }
+        outbox.push(42, 0, "private", start);
+        let sent: Vec<i64> = std::iter::from_fn(|| outbox.pop_ready(start))
+            .map(|x| x.key)
+            .collect();
+        assert_eq!(sent, vec![0, 1, 2, 0]);
+        assert_eq!(outbox.next_ready_in(start), Some(Duration::from_secs(3)));
+        let later = start + Duration::from_secs(3);
+        assert_eq!(outbox.pop_ready(later).map(|x| x.key), Some(3));
+        assert!(outbox.pop_ready(later).is_none());
+    }
+
+    #[test]
+    fn honours_retry_after_and_drops_stale_items() {
+        let start = Instant::now();
+        let mut outbox = Outbox::new(Duration::from_secs(60), start);
+        outbox.push(42, 1, "first", start);
+        outbox.push(42, 2, "second", start);
+        outbox.push(42, 3, "third", start);
+        assert_eq!(outbox.remove(42, 3), Some("third"));
+        assert_eq!(outbox.push(42, 2, "second, edited", start), Some("second"));
+        let first = outbox.pop_ready(start).unwrap();
+        outbox.retry(first, Duration::from_secs(30), start);
+        let later = start + Duration::from_secs(29);
+        assert!(outbox.pop_ready(later).is_none());
+        let later = start + Duration::from_secs(30);
+        let first = outbox.pop_ready(later).unwrap();
+        assert_eq!((first.item, first.attempts), ("first", 1));
+        let later = start + Duration::from_secs(61);
+        let stale = outbox.drop_stale(later);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].item, "second, edited");
+        assert!(outbox.is_empty());
+        // The chat's bucket is full again by then, so it is forgotten.
+        assert!(outbox.chats.is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+use crate::files::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// When a message is worth correcting at all.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    /// The share of letters, in words that convert into recognised ones,
+    /// above which the message is corrected.
+    pub threshold: f32,
+    /// Messages with fewer words are never corrected.
+    pub min_words: usize,
+    /// Messages with fewer letters outside links and code are never
+    /// corrected.
+    pub min_length: usize,
+    /// Messages whose recognised words have fewer letters in total are
+    /// never corrected.
+    pub min_known_letters: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            threshold: 0.5,
+            min_words: 1,
+            min_length: 0,
+            min_known_letters: 3,
+        }
+    }
+}
+
+/// What a chat changed of the global settings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Overrides {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_words: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_known_letters: Option<usize>,
+}
+
+/// Names of the settings as chats set them.
+pub const SETTING_NAMES: &[&str] = &["threshold", "min_words", "min_length", "min_known_letters"];
+
+/// Why a chat's settings weren't changed.
+#[derive(Debug)]
+pub enum SettingsError {
+    /// The change asked for is invalid; the message tells the chat why.
+    Invalid(String),
+    /// Saving failed, so the change was undone.
+    Save(Box<dyn Error>),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Invalid(e) => write!(f, "{}", e),
+            SettingsError::Save(e) => write!(f, "saving the settings failed: {}", e),
+        }
+    }
+}
+
+impl Error for SettingsError {}
+
+/// Per-chat overrides of the global settings, saved to a JSON file after
+/// every change.
+pub struct ChatSettings {
+    path: PathBuf,
+    defaults: Settings,
+    chats: BTreeMap<i64, Overrides>,
+}
+
+impl ChatSettings {
+    /// Loads the overrides saved at `path`, starting with none if the file
+    /// doesn't exist yet.
+    pub fn load(path: &str, defaults: Settings) -> Result<ChatSettings, Box<dyn Error>> {
+        let chats = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("{}: malformed chat settings: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(ChatSettings {
+            path: PathBuf::from(path),
+            defaults,
+            chats,
+        })
+    }
+
+    pub fn get(&self, chat_id: i64) -> Settings {
+        let overrides = match self.chats.get(&chat_id) {
+            Some(overrides) => overrides,
+            None => return self.defaults,
+        };
+        Settings {
+            threshold: overrides.threshold.unwrap_or(self.defaults.threshold),
+            min_words: overrides.min_words.unwrap_or(self.defaults.min_words),
+            min_length: overrides.min_length.unwrap_or(self.defaults.min_length),
+            min_known_letters: overrides
+                .min_known_letters
+                .unwrap_or(self.defaults.min_known_letters),
+        }
+    }
+
+    /// Overrides one setting of a chat, `name` being one of `SETTING_NAMES`.
+    pub fn set(&mut self, chat_id: i64, name: &str, value: &str) -> Result<(), SettingsError> {
+        let bad_value = || SettingsError::Invalid(format!("bad value '{}' for {}", value, name));
+        let mut overrides = self.chats.get(&chat_id).cloned().unwrap_or_default();
+        match name {
+            "threshold" => {
+                let threshold: f32 = value.parse().map_err(|_| bad_value())?;
+                if !(0.0..1.0).contains(&threshold) {
+                    return Err(SettingsError::Invalid(String::from(
+                        "threshold must be at least 0 and below 1",
+                    )));
+                }
+                overrides.threshold = Some(threshold);
+            }
+            "min_words" => overrides.min_words = Some(value.parse().map_err(|_| bad_value())?),
+            "min_length" => overrides.min_length = Some(value.parse().map_err(|_| bad_value())?),
+            "min_known_letters" => {
+                overrides.min_known_letters = Some(value.parse().map_err(|_| bad_value())?)
+            }
+            _ => {
+                return Err(SettingsError::Invalid(format!(
+                    "unknown setting '{}'",
+                    name
+                )))
+            }
+        }
+        self.replace(chat_id, Some(overrides))
+    }
+
+    /// Drops every override of a chat, going back to the global settings.
+    pub fn reset(&mut self, chat_id: i64) -> Result<(), SettingsError> {
+        if !self.chats.contains_key(&chat_id) {
+            return Ok(());
+        }
+        self.replace(chat_id, None)
+    }
+
+    /// Sets or removes the overrides of a chat and saves them, keeping the
+    /// old ones if saving fails.
+    fn replace(&mut self, chat_id: i64, overrides: Option<Overrides>) -> Result<(), SettingsError> {
+        let old = match overrides {
+            Some(overrides) => self.chats.insert(chat_id, overrides),
+            None => self.chats.remove(&chat_id),
+        };
+        let saved = serde_json::to_string_pretty(&self.chats)
+            .map_err(|e| e.into())
+            .and_then(|json| write_atomically(&self.path, json));
+        if let Err(e) = saved {
+            match old {
+                Some(old) => self.chats.insert(chat_id, old),
+                None => self.chats.remove(&chat_id),
+            };
+            return Err(SettingsError::Save(e));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_persist() {
+        let dir = std::env::temp_dir().join(format!("chat-settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+        let path = path.to_str().unwrap();
+        let defaults = Settings::default();
+
+        let mut settings = ChatSettings::load(path, defaults).unwrap();
+        settings.set(-100, "threshold", "0.75").unwrap();
+        settings.set(-100, "min_words", "3").unwrap();
+        settings.set(42, "min_length", "10").unwrap();
+        assert!(settings.set(42, "threshold", "2").is_err());
+        assert!(settings.set(42, "eagerness", "1").is_err());
+
+        let mut settings = ChatSettings::load(path, defaults).unwrap();
+        assert_eq!(
+            settings.get(-100),
+            Settings {
+                threshold: 0.75,
+                min_words: 3,
+                ..defaults
+            }
+        );
+        assert_eq!(settings.get(42).min_length, 10);
+        assert_eq!(settings.get(7), defaults);
+        settings.reset(-100).unwrap();
+        assert_eq!(
+            ChatSettings::load(path, defaults).unwrap().get(-100),
+            defaults
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn changes_that_fail_to_save_are_undone() {
+        let dir =
+            std::env::temp_dir().join(format!("chat-settings-missing-{}", std::process::id()));
+        let path = dir.join("settings.json");
+        let defaults = Settings::default();
+
+        let mut settings = ChatSettings::load(path.to_str().unwrap(), defaults).unwrap();
+        assert!(matches!(
+            settings.set(-100, "min_words", "3"),
+            Err(SettingsError::Save(_))
+        ));
+        assert!(matches!(
+            settings.set(-100, "min_words", "many"),
+            Err(SettingsError::Invalid(_))
+        ));
+        assert_eq!(settings.get(-100), defaults);
+    }
+}
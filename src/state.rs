@@ -0,0 +1,272 @@
+use crate::files::write_atomically;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use std::error::Error;
+use std::path::PathBuf;
+
+// How many of the latest replies are remembered, to edit or delete them when
+// the messages they correct are edited.
+const MAX_TRACKED_REPLIES: usize = 1000;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Saved {
+    /// Every update up to this one is done.
+    last_update_id: i64,
+    /// Later updates that are done too.
+    #[serde(default)]
+    done: BTreeSet<i64>,
+    /// The tracked replies, oldest first.
+    #[serde(default)]
+    replies: VecDeque<SentReply>,
+}
+
+/// The bot's reply to a message, as it was last sent or edited.
+#[derive(Clone, Serialize, Deserialize)]
+struct SentReply {
+    chat_id: i64,
+    message_id: i64,
+    reply_id: i64,
+    text: String,
+}
+
+/// Which updates the bot is done with, saved to a JSON file so that after a
+/// restart it neither loses updates nor answers one twice. An update is done
+/// once its reply is sent or given up on. Polling confirms an update that
+/// isn't done only when too many are held back, so updates whose replies were
+/// still queued are received again after a restart. It also remembers the
+/// latest replies, by the chat and message they correct.
+pub struct UpdateState {
+    path: PathBuf,
+    saved: Saved,
+    // Received, but their replies are still queued.
+    pending: BTreeSet<i64>,
+    changed: bool,
+}
+
+impl UpdateState {
+    /// Loads the state saved at `path`, starting afresh if the file doesn't
+    /// exist yet.
+    pub fn load(path: &str) -> Result<UpdateState, Box<dyn Error>> {
+        let saved = match std::fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("{}: malformed update state: {}", path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Saved::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(UpdateState {
+            path: PathBuf::from(path),
+            saved,
+            pending: BTreeSet::new(),
+            changed: false,
+        })
+    }
+
+    /// Every update up to this one is done.
+    pub fn last_update_id(&self) -> i64 {
+        self.saved.last_update_id
+    }
+
+    /// The `offset` to call getUpdates with. It confirms only the updates
+    /// that are done, so Telegram keeps the rest until they are. Past
+    /// `max_held` updates held back, done or not, getUpdates would return
+    /// nothing but those, so the oldest are confirmed anyway; their queued
+    /// replies are lost if the bot stops before sending them.
+    pub fn offset(&self, max_held: usize) -> i64 {
+        let mut held: Vec<i64> = self
+            .pending
+            .iter()
+            .chain(&self.saved.done)
+            .copied()
+            .collect();
+        if held.len() <= max_held {
+            return self.saved.last_update_id + 1;
+        }
+        held.sort_unstable();
+        held[held.len() - max_held - 1] + 1
+    }
+
+    /// Whether the update was dealt with before and must be skipped.
+    pub fn is_done(&self, update_id: i64) -> bool {
+        update_id <= self.saved.last_update_id || self.saved.done.contains(&update_id)
+    }
+
+    /// Whether the update was received and its reply is still queued.
+    pub fn is_pending(&self, update_id: i64) -> bool {
+        self.pending.contains(&update_id)
+    }
+
+    /// Notes that the update was received and is being dealt with.
+    pub fn start(&mut self, update_id: i64) {
+        self.pending.insert(update_id);
+    }
+
+    /// Notes that the update is done.
+    pub fn finish(&mut self, update_id: i64) {
+        self.pending.remove(&update_id);
+        self.saved.done.insert(update_id);
+        // Updates before the first pending one are all done.
+        let first_pending = self.pending.first().copied().unwrap_or(i64::MAX);
+        let last_done = self
+            .saved
+            .done
+            .range(..first_pending)
+            .next_back()
+            .copied()
+            .unwrap_or_default();
+        let saved = &mut self.saved;
+        saved.last_update_id = saved.last_update_id.max(last_done);
+        saved.done = saved.done.split_off(&(saved.last_update_id + 1));
+        self.changed = true;
+    }
+
+    fn reply_index(&self, chat_id: i64, message_id: i64) -> Option<usize> {
+        self.saved
+            .replies
+            .iter()
+            .position(|x| x.chat_id == chat_id && x.message_id == message_id)
+    }
+
+    /// The id and text of the bot's reply to a message, if it is tracked.
+    pub fn reply(&self, chat_id: i64, message_id: i64) -> Option<(i64, &str)> {
+        let reply = &self.saved.replies[self.reply_index(chat_id, message_id)?];
+        Some((reply.reply_id, &reply.text))
+    }
+
+    /// Notes the bot's reply to a message, as just sent or edited.
+    pub fn set_reply(&mut self, chat_id: i64, message_id: i64, reply_id: i64, text: &str) {
+        self.changed = true;
+        if let Some(index) = self.reply_index(chat_id, message_id) {
+            let reply = &mut self.saved.replies[index];
+            reply.reply_id = reply_id;
+            reply.text = String::from(text);
+            return;
+        }
+        self.saved.replies.push_back(SentReply {
+            chat_id,
+            message_id,
+            reply_id,
+            text: String::from(text),
+        });
+        if self.saved.replies.len() > MAX_TRACKED_REPLIES {
+            self.saved.replies.pop_front();
+        }
+    }
+
+    /// Forgets the bot's reply to a message, once it is deleted.
+    pub fn remove_reply(&mut self, chat_id: i64, message_id: i64) {
+        if let Some(index) = self.reply_index(chat_id, message_id) {
+            self.saved.replies.remove(index);
+            self.changed = true;
+        }
+    }
+
+    /// Saves the state if it changed since it was last saved.
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.changed {
+            write_atomically(&self.path, serde_json::to_string(&self.saved)?)?;
+            self.changed = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_updates_are_received_again() {
+        let dir = std::env::temp_dir().join(format!("update-state-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        let path = path.to_str().unwrap();
+
+        let mut state = UpdateState::load(path).unwrap();
+        for update_id in 10..=13 {
+            state.start(update_id);
+        }
+        state.finish(10);
+        state.finish(12);
+        state.save().unwrap();
+        assert_eq!(state.last_update_id(), 10);
+
+        let mut state = UpdateState::load(path).unwrap();
+        assert_eq!(state.last_update_id(), 10);
+        assert!(state.is_done(10) && state.is_done(12));
+        assert!(!state.is_done(11) && !state.is_done(13));
+        state.start(11);
+        state.start(13);
+        state.finish(11);
+        assert_eq!(state.last_update_id(), 12);
+        state.finish(13);
+        assert_eq!(state.last_update_id(), 13);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn replies_persist() {
+        let dir = std::env::temp_dir().join(format!("update-state-replies-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        let path = path.to_str().unwrap();
+
+        let mut state = UpdateState::load(path).unwrap();
+        state.set_reply(-100, 1, 2, "привет");
+        state.set_reply(-100, 3, 4, "как дела");
+        state.set_reply(-100, 1, 2, "привет мир");
+        state.remove_reply(-100, 3);
+        state.save().unwrap();
+
+        let mut state = UpdateState::load(path).unwrap();
+        assert_eq!(state.reply(-100, 1), Some((2, "привет мир")));
+        assert_eq!(state.reply(-100, 3), None);
+        for message_id in 10..10 + MAX_TRACKED_REPLIES as i64 {
+            state.set_reply(42, message_id, message_id + 1, "");
+        }
+        assert_eq!(state.reply(-100, 1), None);
+        assert_eq!(state.reply(42, 10), Some((11, "")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn queued_replies_survive_a_restart() {
+        let dir = std::env::temp_dir().join(format!("update-state-queued-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        let path = path.to_str().unwrap();
+
+        let mut state = UpdateState::load(path).unwrap();
+        state.start(20);
+        state.start(21);
+        // 21 got no reply, 20's is still queued when the bot stops.
+        state.finish(21);
+        state.save().unwrap();
+        assert!(state.offset(10) <= 20);
+        assert!(state.is_pending(20));
+
+        let state = UpdateState::load(path).unwrap();
+        assert!(state.offset(10) <= 20);
+        assert!(!state.is_done(20) && !state.is_pending(20));
+        assert!(state.is_done(21));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn too_many_held_updates_are_let_go() {
+        let path = std::env::temp_dir().join(format!("update-state-held-{}", std::process::id()));
+        let mut state = UpdateState::load(path.to_str().unwrap()).unwrap();
+        for update_id in 1..=6 {
+            state.start(update_id);
+        }
+        state.finish(2);
+        state.finish(4);
+        assert_eq!(state.offset(6), 1);
+        // 1 is still pending, 2 is done and 3 pending: they are let go.
+        assert_eq!(state.offset(3), 4);
+        assert!(state.is_pending(3));
+        state.finish(1);
+        assert_eq!(state.last_update_id(), 2);
+        assert_eq!(state.offset(6), 3);
+        assert_eq!(state.offset(3), 4);
+    }
+}
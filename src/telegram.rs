@@ -0,0 +1,552 @@
+use crate::entities::MessageEntity;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.telegram.org";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Chat {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub username: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct User {
+    pub id: i64,
+    pub username: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Message {
+    pub message_id: i64,
+    pub chat: Chat,
+    pub from: Option<User>,
+    pub date: i64,
+    pub text: Option<String>,
+    pub entities: Option<Vec<MessageEntity>>,
+    /// The caption of a photo, video, document and so on.
+    pub caption: Option<String>,
+    pub caption_entities: Option<Vec<MessageEntity>>,
+    pub reply_to_message: Option<Box<Message>>,
+}
+
+impl Message {
+    /// The text of the message, or the caption of its media.
+    pub fn text_or_caption(&self) -> Option<&str> {
+        self.text.as_deref().or(self.caption.as_deref())
+    }
+
+    /// The entities of `text_or_caption`.
+    pub fn text_or_caption_entities(&self) -> &[MessageEntity] {
+        let entities = match self.text {
+            Some(_) => &self.entities,
+            None => &self.caption_entities,
+        };
+        entities.as_deref().unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Update {
+    pub update_id: i64,
+    pub message: Option<Message>,
+    /// The new version of a message that was edited.
+    pub edited_message: Option<Message>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMember {
+    pub status: String,
+    pub user: User,
+}
+
+/// Why a request failed and what to do about it, for some errors.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ResponseParameters {
+    pub migrate_to_chat_id: Option<i64>,
+    pub retry_after: Option<u64>,
+}
+
+/// The envelope every Bot API method answers with.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiResponse<T> {
+    pub ok: bool,
+    pub result: Option<T>,
+    pub description: Option<String>,
+    pub error_code: Option<i64>,
+    pub parameters: Option<ResponseParameters>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct GetUpdates {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    /// At most this many updates are returned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Seconds to wait for an update before returning none: long polling.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+    /// Kinds of updates to receive, such as "message".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_updates: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SendMessage<'a> {
+    pub chat_id: i64,
+    pub text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply_to_message_id: Option<i64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct EditMessageText<'a> {
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub text: &'a str,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DeleteMessage {
+    pub chat_id: i64,
+    pub message_id: i64,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct SetWebhook {
+    /// The HTTPS URL Telegram POSTs updates to.
+    pub url: String,
+    /// Sent back in the X-Telegram-Bot-Api-Secret-Token header of every
+    /// update, so the bot knows it comes from Telegram.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_updates: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GetChatMember {
+    pub chat_id: i64,
+    pub user_id: i64,
+}
+
+/// Why a Bot API call failed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApiError {
+    /// The request never got an answer: no connection, a timeout and so on.
+    Network(String),
+    /// The answer wasn't a Bot API response, e.g. an error page of a proxy.
+    Malformed(String),
+    /// 429: too many requests, try again after this many seconds.
+    FloodWait(u64),
+    /// 403: the bot was blocked by the user or kicked from the chat.
+    Forbidden(String),
+    /// 400: e.g. the message to reply to or the chat doesn't exist anymore.
+    BadRequest(String),
+    /// 5xx: the Bot API server is having trouble.
+    Server(i64, String),
+    Other(i64, String),
+}
+
+impl ApiError {
+    fn from_response<T>(response: ApiResponse<T>) -> ApiError {
+        let description = response.description.unwrap_or_default();
+        let retry_after = response.parameters.and_then(|x| x.retry_after);
+        match response.error_code.unwrap_or_default() {
+            429 => ApiError::FloodWait(retry_after.unwrap_or(1)),
+            403 => ApiError::Forbidden(description),
+            400 => ApiError::BadRequest(description),
+            code if code >= 500 => ApiError::Server(code, description),
+            code => ApiError::Other(code, description),
+        }
+    }
+
+    /// Whether the same call may succeed later, as opposed to errors that
+    /// will repeat however many times the call is made.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::Network(_)
+            | ApiError::Malformed(_)
+            | ApiError::FloodWait(_)
+            | ApiError::Server(_, _) => true,
+            ApiError::Forbidden(_) | ApiError::BadRequest(_) | ApiError::Other(_, _) => false,
+        }
+    }
+
+    /// How long the server asked to wait before calling again.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ApiError::FloodWait(seconds) => Some(Duration::from_secs(*seconds)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::Network(e) => write!(f, "network error: {}", e),
+            ApiError::Malformed(e) => write!(f, "malformed response: {}", e),
+            ApiError::FloodWait(seconds) => write!(f, "flood control, retry after {}s", seconds),
+            ApiError::Forbidden(description) => write!(f, "forbidden: {}", description),
+            ApiError::BadRequest(description) => write!(f, "bad request: {}", description),
+            ApiError::Server(code, description) | ApiError::Other(code, description) => {
+                write!(f, "error {}: {}", code, description)
+            }
+        }
+    }
+}
+
+impl Error for ApiError {}
+
+/// Carries a Bot API request to the server, so that tests can answer
+/// requests in-process instead.
+pub trait Transport {
+    /// POSTs `body` of `content_type` to `url` and returns the response body,
+    /// whatever the HTTP status: the Bot API describes its errors in the body.
+    fn post(&self, url: &str, content_type: &str, body: Vec<u8>) -> Result<String, Box<dyn Error>>;
+}
+
+/// Talks to the Bot API over HTTPS, reusing one connection pool.
+pub struct HttpTransport {
+    client: reqwest::blocking::Client,
+}
+
+impl HttpTransport {
+    pub fn new() -> HttpTransport {
+        HttpTransport {
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Gives up on requests after `timeout`, which must be longer than the
+    /// longest poll timeout of `getUpdates`.
+    pub fn with_timeout(timeout: Duration) -> Result<HttpTransport, Box<dyn Error>> {
+        Ok(HttpTransport {
+            client: reqwest::blocking::Client::builder()
+                .timeout(timeout)
+                .build()?,
+        })
+    }
+}
+
+impl Default for HttpTransport {
+    fn default() -> HttpTransport {
+        HttpTransport::new()
+    }
+}
+
+impl Transport for HttpTransport {
+    fn post(&self, url: &str, content_type: &str, body: Vec<u8>) -> Result<String, Box<dyn Error>> {
+        Ok(self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body)
+            .send()
+            .and_then(|x| x.text())
+            // The URL holds the bot's token.
+            .map_err(|e| e.without_url())?)
+    }
+}
+
+/// A bot's client of the Bot API at `base_url`, which is the official server
+/// unless the bot runs against a local Bot API server.
+pub struct Client {
+    transport: Box<dyn Transport>,
+    base_url: String,
+    token: String,
+}
+
+impl Client {
+    pub fn new(transport: Box<dyn Transport>, base_url: &str, token: &str) -> Client {
+        Client {
+            transport,
+            base_url: String::from(base_url.trim_end_matches('/')),
+            token: String::from(token),
+        }
+    }
+
+    fn method_url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", self.base_url, self.token, method)
+    }
+
+    /// Calls `method` and returns its whole response, failed or not.
+    pub fn call<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<ApiResponse<R>, ApiError> {
+        let params = serde_json::to_vec(params)
+            .map_err(|e| ApiError::Other(0, format!("{}: {}", method, e)))?;
+        self.post(method, "application/json", params)
+    }
+
+    fn post<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<ApiResponse<R>, ApiError> {
+        let body = self
+            .transport
+            .post(&self.method_url(method), content_type, body)
+            .map_err(|e| ApiError::Network(e.to_string().replace(&self.token, "<token>")))?;
+        serde_json::from_str(&body).map_err(|e| ApiError::Malformed(format!("{}: {}", method, e)))
+    }
+
+    /// Turns a failed response into an error.
+    fn into_result<R>(method: &str, response: ApiResponse<R>) -> Result<R, ApiError> {
+        match (response.ok, response.result) {
+            (true, Some(result)) => Ok(result),
+            (true, None) => Err(ApiError::Malformed(format!("{}: no result", method))),
+            (false, result) => Err(ApiError::from_response(ApiResponse { result, ..response })),
+        }
+    }
+
+    /// Calls `method` and returns its result, turning failed responses into
+    /// errors.
+    fn result<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &P,
+    ) -> Result<R, ApiError> {
+        Client::into_result(method, self.call(method, params)?)
+    }
+
+    /// The bot itself.
+    pub fn get_me(&self) -> Result<User, ApiError> {
+        self.result("getMe", &serde_json::json!({}))
+    }
+
+    pub fn get_updates(&self, params: &GetUpdates) -> Result<Vec<Update>, ApiError> {
+        self.result("getUpdates", params)
+    }
+
+    pub fn send_message(&self, params: &SendMessage) -> Result<Message, ApiError> {
+        self.result("sendMessage", params)
+    }
+
+    pub fn edit_message_text(&self, params: &EditMessageText) -> Result<Message, ApiError> {
+        self.result("editMessageText", params)
+    }
+
+    pub fn delete_message(&self, params: &DeleteMessage) -> Result<bool, ApiError> {
+        self.result("deleteMessage", params)
+    }
+
+    pub fn get_chat_member(&self, params: &GetChatMember) -> Result<ChatMember, ApiError> {
+        self.result("getChatMember", params)
+    }
+
+    /// Registers the webhook, uploading `certificate`, the public key in PEM,
+    /// when the server behind it has a self-signed one.
+    pub fn set_webhook(
+        &self,
+        params: &SetWebhook,
+        certificate: Option<&[u8]>,
+    ) -> Result<bool, ApiError> {
+        let certificate = match certificate {
+            Some(certificate) => certificate,
+            None => return self.result("setWebhook", params),
+        };
+        let mut fields = Vec::new();
+        if let Ok(serde_json::Value::Object(params)) = serde_json::to_value(params) {
+            for (name, value) in params {
+                let value = match value {
+                    serde_json::Value::String(value) => value,
+                    value => value.to_string(),
+                };
+                fields.push((name, value));
+            }
+        }
+        let body = multipart_body(&fields, ("certificate", "certificate.pem", certificate));
+        let content_type = format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY);
+        Client::into_result("setWebhook", self.post("setWebhook", &content_type, body)?)
+    }
+
+    /// Removes the webhook, which getUpdates refuses to work alongside.
+    pub fn delete_webhook(&self) -> Result<bool, ApiError> {
+        self.result("deleteWebhook", &serde_json::json!({}))
+    }
+}
+
+// Separates the parts of a multipart body; it never occurs in the fields or
+// in a PEM file.
+const MULTIPART_BOUNDARY: &str = "layout-corrector-c5a1d2f0e7b94b3e";
+
+/// Encodes text `fields` and a `(name, filename, contents)` file as
+/// multipart/form-data.
+fn multipart_body(fields: &[(String, String)], file: (&str, &str, &[u8])) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                MULTIPART_BOUNDARY, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    let (name, filename, contents) = file;
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n",
+            MULTIPART_BOUNDARY, name, filename
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(contents);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY).as_bytes());
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Answers each method with a canned response and records the requests.
+    struct FakeServer {
+        responses: Vec<(&'static str, &'static str)>,
+        requests: Rc<RefCell<Vec<(String, serde_json::Value)>>>,
+    }
+
+    impl Transport for FakeServer {
+        fn post(
+            &self,
+            url: &str,
+            _content_type: &str,
+            body: Vec<u8>,
+        ) -> Result<String, Box<dyn Error>> {
+            self.requests
+                .borrow_mut()
+                .push((String::from(url), serde_json::from_slice(&body)?));
+            self.responses
+                .iter()
+                .find(|(method, _)| url.ends_with(method))
+                .map(|(_, response)| String::from(*response))
+                .ok_or_else(|| format!("no such method: {}", url).into())
+        }
+    }
+
+    #[test]
+    fn calls_methods_with_typed_requests_and_responses() {
+        let requests = Rc::new(RefCell::new(Vec::new()));
+        let server = FakeServer {
+            responses: vec![
+                (
+                    "/getUpdates",
+                    r#"{"ok":true,"result":[{"update_id":7,"message":{"message_id":3,
+                        "chat":{"id":-100,"type":"group"},"date":0,"text":"ghbdtn"}}]}"#,
+                ),
+                (
+                    "/sendMessage",
+                    r#"{"ok":false,"error_code":429,"description":"Too Many Requests",
+                        "parameters":{"retry_after":5}}"#,
+                ),
+            ],
+            requests: requests.clone(),
+        };
+        let client = Client::new(Box::new(server), "http://localhost:8081/", "1:abc");
+
+        let updates = client
+            .get_updates(&GetUpdates {
+                offset: Some(7),
+                timeout: Some(30),
+                allowed_updates: Some(vec![String::from("message")]),
+                ..GetUpdates::default()
+            })
+            .unwrap();
+        assert_eq!(updates[0].update_id, 7);
+        let message = updates[0].message.as_ref().unwrap();
+        assert_eq!(message.chat.kind.as_deref(), Some("group"));
+        assert_eq!(message.text.as_deref(), Some("ghbdtn"));
+
+        let error = client
+            .send_message(&SendMessage {
+                chat_id: -100,
+                text: "привет",
+                reply_to_message_id: Some(3),
+            })
+            .unwrap_err();
+        assert_eq!(error, ApiError::FloodWait(5));
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(5)));
+        let response: ApiResponse<Message> = client
+            .call(
+                "sendMessage",
+                &SendMessage {
+                    chat_id: -100,
+                    text: "привет",
+                    reply_to_message_id: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(response.parameters.unwrap().retry_after, Some(5));
+
+        let error = client.get_chat_member(&GetChatMember {
+            chat_id: -100,
+            user_id: 1,
+        });
+        match error {
+            Err(ApiError::Network(e)) => assert!(!e.contains("1:abc")),
+            error => panic!("expected a network error, got {:?}", error),
+        }
+
+        let requests = requests.borrow();
+        assert_eq!(requests[0].0, "http://localhost:8081/bot1:abc/getUpdates");
+        assert_eq!(
+            requests[0].1,
+            serde_json::json!({"offset": 7, "timeout": 30, "allowed_updates": ["message"]})
+        );
+        assert_eq!(
+            requests[1].1,
+            serde_json::json!({"chat_id": -100, "text": "привет", "reply_to_message_id": 3})
+        );
+    }
+
+    #[test]
+    fn reads_captions() {
+        let update: Update =
+            serde_json::from_str(include_str!("../tests/fixtures/updates/photo.json")).unwrap();
+        let message = update.message.unwrap();
+        assert_eq!(
+            message.text_or_caption(),
+            Some("cvjnhb rfr rhfcbdj t.me/test")
+        );
+        assert_eq!(message.text_or_caption_entities()[0].kind, "url");
+    }
+
+    #[test]
+    fn classifies_errors() {
+        let error = |response: &str| {
+            ApiError::from_response(serde_json::from_str::<ApiResponse<()>>(response).unwrap())
+        };
+        let blocked = error(
+            r#"{"ok":false,"error_code":403,"description":"Forbidden: bot was blocked by the user"}"#,
+        );
+        assert!(matches!(blocked, ApiError::Forbidden(_)));
+        assert!(!blocked.is_retryable());
+        let not_found = error(
+            r#"{"ok":false,"error_code":400,"description":"Bad Request: message to reply not found"}"#,
+        );
+        assert!(!not_found.is_retryable());
+        assert!(
+            error(r#"{"ok":false,"error_code":502,"description":"Bad Gateway"}"#).is_retryable()
+        );
+        assert!(
+            error(r#"{"ok":false,"error_code":429,"description":"Too Many Requests"}"#)
+                .is_retryable()
+        );
+    }
+}
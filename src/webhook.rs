@@ -0,0 +1,241 @@
+use crate::telegram::Update;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+/// The header Telegram sends the webhook's secret token in.
+pub const SECRET_HEADER: &str = "X-Telegram-Bot-Api-Secret-Token";
+
+// Requests larger than this aren't updates.
+const MAX_REQUEST: u64 = 1 << 20;
+
+// How long a connection may take to send its whole request. Requests are
+// read one at a time, so a slow one holds up the others this long at most.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How often the listener is checked for connections while waiting.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+const BAD_REQUEST: &str = "400 Bad Request";
+
+/// Reads from a connection until a deadline, however slowly it sends.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Compares in a time that doesn't depend on where the strings differ, so
+/// that the secret can't be guessed byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Receives the updates Telegram POSTs to the webhook at `path`. It speaks
+/// plain HTTP: TLS is left to a reverse proxy in front of it.
+pub struct WebhookServer {
+    listener: TcpListener,
+    path: String,
+    secret: String,
+}
+
+impl WebhookServer {
+    /// Listens on `address`, accepting updates only at `path` and only with
+    /// `secret` in their `SECRET_HEADER`.
+    pub fn bind(address: &str, path: &str, secret: &str) -> Result<WebhookServer, Box<dyn Error>> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("{}: {}", address, e))?;
+        listener.set_nonblocking(true)?;
+        Ok(WebhookServer {
+            listener,
+            path: String::from(path),
+            secret: String::from(secret),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, Box<dyn Error>> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Waits up to `timeout` for the next update, answering requests that
+    /// don't carry one with an error status. A stream of such requests
+    /// doesn't hold it up past `timeout`, plus one request at most.
+    pub fn next_update(&self, timeout: Duration) -> Option<Update> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => match self.handle(stream) {
+                    Ok(update) => return Some(update),
+                    Err(status) => println!("Webhook request from {} rejected: {}", peer, status),
+                },
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(ACCEPT_INTERVAL);
+                }
+                Err(e) => {
+                    println!("Accepting a webhook connection failed: {}", e);
+                    std::thread::sleep(ACCEPT_INTERVAL);
+                }
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+        }
+    }
+
+    /// Reads a request and answers it: 200 when it carries an update, which
+    /// is returned, and the error status otherwise.
+    fn handle(&self, stream: TcpStream) -> Result<Update, &'static str> {
+        let result = self.read_update(&stream);
+        let status = match &result {
+            Ok(_) => "200 OK",
+            Err(status) => status,
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        );
+        if let Err(e) = (&stream).write_all(response.as_bytes()) {
+            println!("Answering a webhook request failed: {}", e);
+        }
+        result
+    }
+
+    fn read_update(&self, stream: &TcpStream) -> Result<Update, &'static str> {
+        stream.set_nonblocking(false).map_err(|_| BAD_REQUEST)?;
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        let mut reader = BufReader::new(DeadlineReader { stream, deadline }.take(MAX_REQUEST));
+        let mut line = String::new();
+        reader.read_line(&mut line).map_err(|_| BAD_REQUEST)?;
+        let mut request_line = line.split_whitespace();
+        let method = request_line.next().unwrap_or_default();
+        let target = request_line.next().unwrap_or_default();
+
+        let mut secret = None;
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).map_err(|_| BAD_REQUEST)?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let (name, value) = header.split_once(':').ok_or(BAD_REQUEST)?;
+            let value = value.trim();
+            if name.eq_ignore_ascii_case(SECRET_HEADER) {
+                secret = Some(String::from(value));
+            } else if name.eq_ignore_ascii_case("Content-Length") {
+                length = Some(value.parse::<u64>().map_err(|_| BAD_REQUEST)?);
+            }
+        }
+
+        if target.split('?').next() != Some(self.path.as_str()) {
+            return Err("404 Not Found");
+        }
+        if method != "POST" {
+            return Err("405 Method Not Allowed");
+        }
+        let secret = secret.unwrap_or_default();
+        if !constant_time_eq(secret.as_bytes(), self.secret.as_bytes()) {
+            return Err("401 Unauthorized");
+        }
+        let length = length.ok_or("411 Length Required")?;
+        if length > MAX_REQUEST {
+            return Err("413 Payload Too Large");
+        }
+        let mut body = vec![0; length as usize];
+        reader.read_exact(&mut body).map_err(|_| BAD_REQUEST)?;
+        serde_json::from_slice(&body).map_err(|_| BAD_REQUEST)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(address: SocketAddr, secret: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /hook HTTP/1.1\r\nHost: localhost\r\n{}: {}\r\n\
+             Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            SECRET_HEADER,
+            secret,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn accepts_only_updates_with_the_secret() {
+        let server = WebhookServer::bind("127.0.0.1:0", "/hook", "s3cret").unwrap();
+        let address = server.local_addr().unwrap();
+        let update = include_str!("../tests/fixtures/updates/message.json");
+        let client = std::thread::spawn(move || {
+            (
+                post(address, "guess", update),
+                post(address, "s3cret", update),
+            )
+        });
+        let received = server.next_update(Duration::from_secs(10)).unwrap();
+        let (rejected, accepted) = client.join().unwrap();
+        assert!(rejected.starts_with("HTTP/1.1 401"));
+        assert!(accepted.starts_with("HTTP/1.1 200"));
+        assert_eq!(received.update_id, 815273451);
+        let message = received.message.unwrap();
+        assert_eq!(message.text.as_deref(), Some("ghbdtn, rfr ltkf?"));
+        assert!(server.next_update(Duration::from_millis(10)).is_none());
+    }
+
+    #[test]
+    fn rejected_requests_dont_hold_it_up() {
+        let server = WebhookServer::bind("127.0.0.1:0", "/hook", "s3cret").unwrap();
+        let address = server.local_addr().unwrap();
+        // Connections that never send their requests, each rejected only
+        // when it times out.
+        let silent: Vec<TcpStream> = (0..3)
+            .map(|_| TcpStream::connect(address).unwrap())
+            .collect();
+        let started = Instant::now();
+        assert!(server.next_update(Duration::from_millis(100)).is_none());
+        assert!(started.elapsed() < 2 * REQUEST_TIMEOUT);
+        drop(silent);
+    }
+
+    #[test]
+    fn a_slow_request_times_out() {
+        let server = WebhookServer::bind("127.0.0.1:0", "/hook", "s3cret").unwrap();
+        let address = server.local_addr().unwrap();
+        let update = include_str!("../tests/fixtures/updates/message.json");
+        let client = std::thread::spawn(move || {
+            // Sends a byte a second, never finishing its request.
+            let mut slow = TcpStream::connect(address).unwrap();
+            let started = Instant::now();
+            for byte in b"POST /hook HTTP/1.1\r\nHost: localhost\r\n".iter().cycle() {
+                if slow.write_all(&[*byte]).is_err() || started.elapsed() > 3 * REQUEST_TIMEOUT {
+                    break;
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            post(address, "s3cret", update)
+        });
+        let started = Instant::now();
+        let received = server.next_update(3 * REQUEST_TIMEOUT).unwrap();
+        assert!(started.elapsed() < 2 * REQUEST_TIMEOUT);
+        assert_eq!(received.update_id, 815273451);
+        assert!(client.join().unwrap().starts_with("HTTP/1.1 200"));
+    }
+}
@@ -0,0 +1,500 @@
+use crate::keysyms::KEYSYMS;
+use crate::layout::Layout;
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SYMBOLS_DIR: &str = "/usr/share/X11/xkb/symbols";
+
+// Layouts only ever need the two Shift levels; level 3 and 4 are AltGr
+// extras that nobody mistypes whole words with.
+const LEVELS: usize = 2;
+
+// The `qwerty` section of keycodes/aliases, used by phonetic layouts.
+const ALIASES: &[(&str, &str)] = &[
+    ("LatQ", "AD01"),
+    ("LatW", "AD02"),
+    ("LatE", "AD03"),
+    ("LatR", "AD04"),
+    ("LatT", "AD05"),
+    ("LatY", "AD06"),
+    ("LatU", "AD07"),
+    ("LatI", "AD08"),
+    ("LatO", "AD09"),
+    ("LatP", "AD10"),
+    ("LatA", "AC01"),
+    ("LatS", "AC02"),
+    ("LatD", "AC03"),
+    ("LatF", "AC04"),
+    ("LatG", "AC05"),
+    ("LatH", "AC06"),
+    ("LatJ", "AC07"),
+    ("LatK", "AC08"),
+    ("LatL", "AC09"),
+    ("LatZ", "AB01"),
+    ("LatX", "AB02"),
+    ("LatC", "AB03"),
+    ("LatV", "AB04"),
+    ("LatB", "AB05"),
+    ("LatN", "AB06"),
+    ("LatM", "AB07"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    KeyName(String),
+    Punct(char),
+}
+
+struct Section {
+    name: String,
+    is_default: bool,
+    body: Vec<Token>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Merge {
+    Override,
+    Augment,
+}
+
+/// Reads layouts out of an XKB `symbols` directory, following `include`
+/// directives between files the same way `setxkbmap` does.
+pub struct SymbolsDir {
+    dir: PathBuf,
+}
+
+impl SymbolsDir {
+    pub fn new<P: AsRef<Path>>(dir: P) -> SymbolsDir {
+        SymbolsDir {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Loads a layout given as `file` or `file(variant)`, e.g. `ua` or
+    /// `ru(phonetic)`.
+    pub fn load(&self, spec: &str) -> Result<Layout, Box<dyn Error>> {
+        let mut keys = BTreeMap::new();
+        self.merge_spec(spec, Merge::Override, &mut keys, &mut HashSet::new())?;
+        Ok(Layout::from_keys(spec, keys))
+    }
+
+    fn merge_spec(
+        &self,
+        spec: &str,
+        merge: Merge,
+        keys: &mut BTreeMap<String, Vec<char>>,
+        seen: &mut HashSet<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (file, variant) = parse_spec(spec)?;
+        let sections = parse_file(&self.read(file)?)?;
+        let section = match variant {
+            Some(variant) => sections.iter().find(|x| x.name == variant),
+            None => sections
+                .iter()
+                .find(|x| x.is_default)
+                .or_else(|| sections.first()),
+        }
+        .ok_or_else(|| format!("no xkb_symbols section for '{}'", spec))?;
+        let qualified = format!("{}({})", file, section.name);
+        if !seen.insert(qualified.clone()) {
+            return Err(format!("recursive include of '{}'", qualified).into());
+        }
+        self.merge_section(section, merge, keys, seen)?;
+        seen.remove(&qualified);
+        Ok(())
+    }
+
+    fn merge_section(
+        &self,
+        section: &Section,
+        merge: Merge,
+        keys: &mut BTreeMap<String, Vec<char>>,
+        seen: &mut HashSet<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut tokens = section.body.iter().peekable();
+        while let Some(mut token) = tokens.next() {
+            // `augment`, `override` and `replace` prefix both includes and keys.
+            let mut statement_merge = merge;
+            if let Token::Ident(word) = token {
+                let prefix = match word.as_str() {
+                    "augment" => Some(Merge::Augment),
+                    "override" | "replace" => Some(Merge::Override),
+                    _ => None,
+                };
+                if let (Some(prefix), Some(next)) = (prefix, tokens.peek()) {
+                    statement_merge = prefix;
+                    // `augment "file"` includes, `augment key ...` merges a key.
+                    if let Token::Ident(_) | Token::Str(_) = next {
+                        token = tokens.next().unwrap();
+                    }
+                }
+            }
+            match token {
+                // Includes end with their file string, without a `;`.
+                Token::Str(includes) => {
+                    self.merge_includes(includes, statement_merge, keys, seen)?;
+                }
+                Token::Ident(word) if word == "include" => {
+                    if let Some(Token::Str(includes)) = tokens.peek() {
+                        tokens.next();
+                        self.merge_includes(includes, statement_merge, keys, seen)?;
+                    }
+                }
+                Token::Punct(';') => (),
+                Token::Ident(word) if word == "key" => {
+                    let name = match tokens.peek() {
+                        Some(Token::KeyName(name)) => name,
+                        // `key.type[Group1] = ...` and other defaults.
+                        _ => {
+                            skip_statement(&mut tokens);
+                            continue;
+                        }
+                    };
+                    tokens.next();
+                    let mut body = Vec::new();
+                    let mut depth = 0;
+                    for token in tokens.by_ref() {
+                        match token {
+                            Token::Punct('{') => depth += 1,
+                            Token::Punct('}') => depth -= 1,
+                            Token::Punct(';') if depth == 0 => break,
+                            _ => (),
+                        }
+                        body.push(token.clone());
+                    }
+                    let levels = parse_key_levels(&body)?;
+                    if levels.is_empty() {
+                        continue;
+                    }
+                    let position = resolve_alias(name);
+                    if statement_merge == Merge::Override || !keys.contains_key(&position) {
+                        keys.insert(position, levels);
+                    }
+                }
+                _ => skip_statement(&mut tokens),
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_includes(
+        &self,
+        includes: &str,
+        merge: Merge,
+        keys: &mut BTreeMap<String, Vec<char>>,
+        seen: &mut HashSet<String>,
+    ) -> Result<(), Box<dyn Error>> {
+        // "us(basic)+level3(ralt_switch)|foo": '+' overrides, '|' augments.
+        let mut merge = merge;
+        let mut start = 0;
+        for (i, c) in includes
+            .char_indices()
+            .chain(std::iter::once((includes.len(), '+')))
+        {
+            if c == '+' || c == '|' {
+                let spec = &includes[start..i];
+                if !spec.is_empty() {
+                    self.merge_spec(spec, merge, keys, seen)?;
+                }
+                merge = if c == '|' {
+                    Merge::Augment
+                } else {
+                    Merge::Override
+                };
+                start = i + 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&self, file: &str) -> Result<String, Box<dyn Error>> {
+        let path = self.dir.join(file);
+        let mut buf = String::new();
+        File::open(&path)
+            .and_then(|mut x| x.read_to_string(&mut buf))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(buf)
+    }
+}
+
+fn parse_spec(spec: &str) -> Result<(&str, Option<&str>), Box<dyn Error>> {
+    // A trailing ":2" assigns the include to another group; we only read Group1.
+    let spec = spec.split(':').next().unwrap_or(spec).trim();
+    match spec.find('(') {
+        Some(open) if spec.ends_with(')') => {
+            Ok((&spec[..open], Some(&spec[open + 1..spec.len() - 1])))
+        }
+        Some(_) => Err(format!("malformed layout name '{}'", spec).into()),
+        None => Ok((spec, None)),
+    }
+}
+
+fn resolve_alias(name: &str) -> String {
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map(|(_, position)| String::from(*position))
+        .unwrap_or_else(|| String::from(name))
+}
+
+fn skip_statement<'a, I: Iterator<Item = &'a Token>>(tokens: &mut I) {
+    let mut depth = 0;
+    for token in tokens {
+        match token {
+            Token::Punct('{') => depth += 1,
+            Token::Punct('}') => depth -= 1,
+            Token::Punct(';') if depth <= 0 => return,
+            _ => (),
+        }
+    }
+}
+
+/// Picks the Group1 symbols out of a key body such as `[ a, A ]`,
+/// `[ a, A ], [ b, B ]` or `type= "FOUR_LEVEL", symbols[Group1]= [ a, A ]`.
+fn parse_key_levels(body: &[Token]) -> Result<Vec<char>, Box<dyn Error>> {
+    let mut i = 0;
+    let mut depth = 0;
+    let mut symbols = None;
+    while i < body.len() {
+        match &body[i] {
+            Token::Punct('{') => depth += 1,
+            Token::Punct('}') => depth -= 1,
+            Token::Punct('[') if depth == 1 => {
+                let end = i + body[i..]
+                    .iter()
+                    .position(|x| *x == Token::Punct(']'))
+                    .ok_or("unterminated symbol list")?;
+                // Either a bare `[ a, A ]` right after `{` or `,`, or the value
+                // of `symbols[Group1]=`.
+                let is_bare_list = matches!(body[i - 1], Token::Punct('{') | Token::Punct(','));
+                let is_symbols_assignment = i >= 5
+                    && body[i - 1] == Token::Punct('=')
+                    && body[i - 2] == Token::Punct(']')
+                    && is_ident(&body[i - 3], "group1")
+                    && body[i - 4] == Token::Punct('[')
+                    && is_ident(&body[i - 5], "symbols");
+                if symbols.is_none() && (is_symbols_assignment || is_bare_list) {
+                    symbols = Some(&body[i + 1..end]);
+                }
+                i = end;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    let mut levels = Vec::new();
+    for token in symbols.unwrap_or(&[]) {
+        if let Token::Ident(keysym) = token {
+            match keysym_to_char(keysym) {
+                Some(c) if levels.len() < LEVELS => levels.push(c),
+                _ => break,
+            }
+        }
+    }
+    Ok(levels)
+}
+
+fn is_ident(token: &Token, expected: &str) -> bool {
+    matches!(token, Token::Ident(word) if word.eq_ignore_ascii_case(expected))
+}
+
+fn keysym_to_char(keysym: &str) -> Option<char> {
+    if let Ok(i) = KEYSYMS.binary_search_by(|(name, _)| (*name).cmp(keysym)) {
+        return Some(KEYSYMS[i].1);
+    }
+    let code = if let Some(hex) = keysym.strip_prefix("0x") {
+        // Raw keysyms are Unicode only in Latin-1 and above 0x01000000.
+        match u32::from_str_radix(hex, 16).ok()? {
+            code if code >= 0x0100_0000 => code - 0x0100_0000,
+            code if code < 0x100 => code,
+            _ => return None,
+        }
+    } else if keysym.len() > 4 && keysym.starts_with('U') {
+        u32::from_str_radix(&keysym[1..], 16).ok()?
+    } else {
+        return None;
+    };
+    std::char::from_u32(code)
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => (),
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|x| *x == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for x in chars.by_ref() {
+                    if previous == '*' && x == '/' {
+                        break;
+                    }
+                    previous = x;
+                }
+            }
+            '#' => {
+                chars.by_ref().find(|x| *x == '\n');
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(x) => value.push(x),
+                        None => return Err("unterminated string".into()),
+                    }
+                }
+                tokens.push(Token::Str(value));
+            }
+            '<' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('>') => break,
+                        Some(x) => value.push(x),
+                        None => return Err("unterminated key name".into()),
+                    }
+                }
+                tokens.push(Token::KeyName(value));
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let mut value = c.to_string();
+                while let Some(&x) = chars.peek() {
+                    if x.is_alphanumeric() || x == '_' {
+                        value.push(x);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(value));
+            }
+            _ => tokens.push(Token::Punct(c)),
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_file(src: &str) -> Result<Vec<Section>, Box<dyn Error>> {
+    let tokens = tokenize(src)?;
+    let mut sections = Vec::new();
+    let mut is_default = false;
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Ident(word) if word == "default" => is_default = true,
+            Token::Ident(word) if word == "xkb_symbols" => {
+                let name = match tokens.get(i + 1) {
+                    Some(Token::Str(name)) => name.clone(),
+                    _ => return Err("xkb_symbols without a name".into()),
+                };
+                if tokens.get(i + 2) != Some(&Token::Punct('{')) {
+                    return Err(format!("xkb_symbols \"{}\" has no body", name).into());
+                }
+                let mut depth = 0;
+                let mut end = i + 2;
+                while end < tokens.len() {
+                    match tokens[end] {
+                        Token::Punct('{') => depth += 1,
+                        Token::Punct('}') => depth -= 1,
+                        _ => (),
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    end += 1;
+                }
+                sections.push(Section {
+                    name,
+                    is_default,
+                    body: tokens[i + 3..end.min(tokens.len())].to_vec(),
+                });
+                is_default = false;
+                i = end;
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+    Ok(sections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::LayoutPair;
+
+    fn fixtures() -> SymbolsDir {
+        SymbolsDir::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/xkb"))
+    }
+
+    #[test]
+    fn matches_builtin_layouts() {
+        let us = fixtures().load("us").unwrap();
+        let ru = fixtures().load("ru").unwrap();
+        let to_russian = LayoutPair::new(&us, &ru);
+        let builtin = LayoutPair::new(
+            &Layout::builtin("us").unwrap(),
+            &Layout::builtin("ru").unwrap(),
+        );
+        let text = "Ghbdtn& rfr ltkf? @#$^ {}:\"<>~`";
+        assert_eq!(to_russian.convert(text), builtin.convert(text));
+    }
+
+    #[test]
+    fn resolves_includes_and_variants() {
+        let us = fixtures().load("us").unwrap();
+        let ua = fixtures().load("ua").unwrap();
+        assert_eq!(LayoutPair::new(&us, &ua).convert("ghbdsn ]"), "привіт ї");
+        let by = fixtures().load("by").unwrap();
+        assert_eq!(
+            LayoutPair::new(&us, &by).convert("ghsdbnfyyt o"),
+            "прывітанне ў"
+        );
+        let phonetic = fixtures().load("ru(phonetic)").unwrap();
+        assert_eq!(LayoutPair::new(&us, &phonetic).convert("priwet"), "привет");
+        let dvorak = fixtures().load("us(dvorak)").unwrap();
+        assert_eq!(LayoutPair::new(&us, &dvorak).convert("jdpps"), "hello");
+    }
+
+    #[test]
+    fn matches_builtin_cyrillic_layouts() {
+        for name in &["ua", "by", "kz"] {
+            let imported = fixtures().load(name).unwrap();
+            let builtin = Layout::builtin(name).unwrap();
+            assert_eq!(imported.letters(), builtin.letters(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn reads_kazakh_letters() {
+        let us = fixtures().load("us").unwrap();
+        let kz = fixtures().load("kz").unwrap();
+        assert_eq!(LayoutPair::new(&us, &kz).convert("2"), "ә");
+    }
+
+    #[test]
+    fn keys_follow_includes() {
+        let us = fixtures().load("us").unwrap();
+        let after_include = fixtures().load("includes(key_after_include)").unwrap();
+        assert_eq!(LayoutPair::new(&us, &after_include).convert("qwe"), "awe");
+        let merge_modes = fixtures().load("includes(merge_mode_includes)").unwrap();
+        assert_eq!(LayoutPair::new(&us, &merge_modes).convert("qwe"), "xwy");
+    }
+
+    #[test]
+    fn unknown_variant_is_an_error() {
+        assert!(fixtures().load("ru(no_such_variant)").is_err());
+        assert!(fixtures().load("no_such_file").is_err());
+    }
+}